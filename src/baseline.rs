@@ -0,0 +1,193 @@
+//! Baseline of known findings, so only new findings are reported
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::security::{Alert, BaselineState};
+
+/// Version of the baseline file format
+const BASELINE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Findings that were present when the baseline was written
+pub struct Baseline {
+    pub version: u32,
+    pub findings: Vec<BaselineFinding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Finding in a baseline
+pub struct BaselineFinding {
+    /// Fingerprint of the alert ID, file and compose key
+    pub fingerprint: String,
+    /// Alert ID
+    pub id: String,
+    /// File, relative to the base path
+    pub path: PathBuf,
+    /// Compose key of the finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Service of the finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Details of the alert when the baseline was written
+    pub details: String,
+}
+
+impl BaselineFinding {
+    pub fn new(alert: &Alert, base: &Path) -> Self {
+        let path = alert
+            .path
+            .path
+            .strip_prefix(base)
+            .unwrap_or(&alert.path.path)
+            .to_path_buf();
+        let id = alert.id.to_string();
+
+        BaselineFinding {
+            fingerprint: fingerprint(&id, &path, alert.path.key.as_deref()),
+            id,
+            path,
+            key: alert.path.key.clone(),
+            service: alert.service.clone(),
+            details: alert.details.clone(),
+        }
+    }
+}
+
+impl Baseline {
+    /// Baseline of the alerts, suppressed alerts are left out
+    pub fn new(alerts: &[Alert], base: &Path) -> Self {
+        let mut findings: Vec<BaselineFinding> = alerts
+            .iter()
+            .filter(|a| a.suppression.is_none())
+            .map(|a| BaselineFinding::new(a, base))
+            .collect();
+        findings.sort_by(|a, b| (&a.path, &a.key, &a.id).cmp(&(&b.path, &b.key, &b.id)));
+
+        Baseline {
+            version: BASELINE_VERSION,
+            findings,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Unable to open baseline: {}", path.display()))?;
+        serde_json::from_reader(file)
+            .with_context(|| format!("Invalid baseline: {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, &self)?;
+        Ok(())
+    }
+
+    /// Set the baseline state of the alerts and return the findings of the
+    /// baseline that no longer exist (fixed)
+    pub fn compare(&self, alerts: &mut [Alert], base: &Path) -> Vec<BaselineFinding> {
+        // Several alerts can share a fingerprint, so match them by count
+        let mut remaining: HashMap<&str, Vec<&BaselineFinding>> = HashMap::new();
+        for finding in &self.findings {
+            remaining
+                .entry(finding.fingerprint.as_str())
+                .or_default()
+                .push(finding);
+        }
+
+        for alert in alerts.iter_mut() {
+            let finding = BaselineFinding::new(alert, base);
+            let matched = remaining
+                .get_mut(finding.fingerprint.as_str())
+                .and_then(|findings| findings.pop())
+                .is_some();
+
+            alert.baseline = Some(match matched {
+                true => BaselineState::Unchanged,
+                false => BaselineState::New,
+            });
+        }
+
+        let mut fixed: Vec<BaselineFinding> = remaining.into_values().flatten().cloned().collect();
+        fixed.sort_by(|a, b| (&a.path, &a.key, &a.id).cmp(&(&b.path, &b.key, &b.id)));
+        fixed
+    }
+}
+
+/// Stable fingerprint (FNV-1a) of an alert ID, file and compose key. Line
+/// numbers are left out so findings survive unrelated edits.
+fn fingerprint(id: &str, path: &Path, key: Option<&str>) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let data = format!("{id}\0{path}\0{}", key.unwrap_or_default());
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::{AlertLocation, Region, RuleID};
+
+    fn alert(id: &str, key: &str, line: i32) -> Alert {
+        Alert {
+            id: RuleID::Quibble(id.to_string()),
+            details: format!("{id} details"),
+            path: AlertLocation {
+                path: PathBuf::from("/srv/app/compose.yml"),
+                region: Some(Region {
+                    start_line: line,
+                    ..Default::default()
+                }),
+                key: Some(key.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let base = Path::new("/srv");
+        let baseline = Baseline::new(
+            &[
+                alert("PRIVILEGED_CONTAINER", "services.web.privileged", 4),
+                alert("DOCKER_SOCKET", "services.web.volumes[0]", 6),
+                alert("KERNEL_PARAMETERS", "services.web.sysctls", 8),
+            ],
+            base,
+        );
+        assert_eq!(baseline.findings[0].path, PathBuf::from("app/compose.yml"));
+
+        // Lines moved, socket fixed and a second sysctls alert added
+        let mut alerts = vec![
+            alert("PRIVILEGED_CONTAINER", "services.web.privileged", 10),
+            alert("KERNEL_PARAMETERS", "services.web.sysctls", 12),
+            alert("KERNEL_PARAMETERS", "services.web.sysctls", 12),
+            alert("PRIVILEGED_CONTAINER", "services.db.privileged", 20),
+        ];
+        let fixed = baseline.compare(&mut alerts, base);
+
+        let states: Vec<BaselineState> = alerts.iter().map(|a| a.baseline.unwrap()).collect();
+        assert_eq!(
+            states,
+            vec![
+                BaselineState::Unchanged,
+                BaselineState::Unchanged,
+                BaselineState::New,
+                BaselineState::New
+            ]
+        );
+        assert_eq!(fixed.len(), 1);
+        assert_eq!(fixed[0].id, "DOCKER_SOCKET");
+    }
+}
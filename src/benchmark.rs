@@ -0,0 +1,458 @@
+//! Compliance reports for security benchmarks (`--benchmark`)
+//!
+//! Rules list the benchmark controls their alerts cover. For every service,
+//! a control fails when the service has an alert covering it, passes when an
+//! enabled rule covers it, and is not applicable otherwise (it can not be
+//! checked from a compose file). Suppressed alerts do not fail a control, and
+//! a control is not evaluated when a rule covering it errored on the file.
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    compose::ComposeFile,
+    rules::Rules,
+    security::{Alert, Diagnostic},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// Supported benchmarks
+pub enum Benchmark {
+    /// CIS Docker Benchmark, section 5 (Container Runtime)
+    CisDocker,
+}
+
+/// CIS Docker Benchmark v1.6.0, section 5 (Container Runtime)
+const CIS_DOCKER: &[(&str, &str)] = &[
+    ("5.1", "Ensure swarm mode is not Enabled, if not needed"),
+    (
+        "5.2",
+        "Ensure that, if applicable, an AppArmor Profile is enabled",
+    ),
+    (
+        "5.3",
+        "Ensure that, if applicable, SELinux security options are set",
+    ),
+    (
+        "5.4",
+        "Ensure that Linux kernel capabilities are restricted within containers",
+    ),
+    ("5.5", "Ensure that privileged containers are not used"),
+    (
+        "5.6",
+        "Ensure sensitive host system directories are not mounted on containers",
+    ),
+    ("5.7", "Ensure sshd is not run within containers"),
+    (
+        "5.8",
+        "Ensure privileged ports are not mapped within containers",
+    ),
+    (
+        "5.9",
+        "Ensure that only needed ports are open on the container",
+    ),
+    (
+        "5.10",
+        "Ensure that the host's network namespace is not shared",
+    ),
+    (
+        "5.11",
+        "Ensure that the memory usage for containers is limited",
+    ),
+    (
+        "5.12",
+        "Ensure that CPU priority is set appropriately on containers",
+    ),
+    (
+        "5.13",
+        "Ensure that the container's root filesystem is mounted as read only",
+    ),
+    (
+        "5.14",
+        "Ensure that incoming container traffic is bound to a specific host interface",
+    ),
+    (
+        "5.15",
+        "Ensure that the 'on-failure' container restart policy is set to '5'",
+    ),
+    (
+        "5.16",
+        "Ensure that the host's process namespace is not shared",
+    ),
+    ("5.17", "Ensure that the host's IPC namespace is not shared"),
+    (
+        "5.18",
+        "Ensure that host devices are not directly exposed to containers",
+    ),
+    (
+        "5.19",
+        "Ensure that the default ulimit is overwritten at runtime if needed",
+    ),
+    ("5.20", "Ensure mount propagation mode is not set to shared"),
+    ("5.21", "Ensure that the host's UTS namespace is not shared"),
+    ("5.22", "Ensure the default seccomp profile is not Disabled"),
+    (
+        "5.23",
+        "Ensure that docker exec commands are not used with the privileged option",
+    ),
+    (
+        "5.24",
+        "Ensure that docker exec commands are not used with the user=root option",
+    ),
+    ("5.25", "Ensure that cgroup usage is confirmed"),
+    (
+        "5.26",
+        "Ensure that the container is restricted from acquiring additional privileges",
+    ),
+    ("5.27", "Ensure that container health is checked at runtime"),
+    (
+        "5.28",
+        "Ensure that Docker commands always make use of the latest version of their image",
+    ),
+    ("5.29", "Ensure that the PIDs cgroup limit is used"),
+    (
+        "5.30",
+        "Ensure that Docker's default bridge \"docker0\" is not used",
+    ),
+    (
+        "5.31",
+        "Ensure that the host's user namespaces are not shared",
+    ),
+    (
+        "5.32",
+        "Ensure that the Docker socket is not mounted inside any containers",
+    ),
+];
+
+impl Benchmark {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Benchmark::CisDocker => "CIS Docker Benchmark v1.6.0",
+        }
+    }
+
+    /// ID and title of the controls
+    pub fn controls(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Benchmark::CisDocker => CIS_DOCKER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+/// Result of a control for a service
+pub enum Status {
+    Pass,
+    Fail,
+    NotApplicable,
+    /// A rule covering the control errored
+    NotEvaluated,
+}
+
+#[derive(Debug, Default, Serialize)]
+/// Compliance report of every service
+pub struct Report {
+    pub benchmark: String,
+    /// Percentage of the applicable controls that pass
+    pub score: f64,
+    pub passed: usize,
+    pub failed: usize,
+    pub not_applicable: usize,
+    pub not_evaluated: usize,
+    pub services: Vec<ServiceReport>,
+    /// Files that failed to load and rules that errored
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ReportError>,
+    /// Alert IDs covering each control, of the enabled rules
+    #[serde(skip)]
+    covered: HashMap<String, Vec<String>>,
+    /// Rule reporting each alert ID
+    #[serde(skip)]
+    rules: HashMap<String, String>,
+    #[serde(skip)]
+    controls: &'static [(&'static str, &'static str)],
+    #[serde(skip)]
+    base: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceReport {
+    /// Files of the compose project, as merged
+    pub files: Vec<String>,
+    pub service: String,
+    /// Percentage of the applicable controls that pass
+    pub score: f64,
+    pub controls: Vec<ControlResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ControlResult {
+    pub id: String,
+    pub title: String,
+    pub status: Status,
+    /// Alerts that fail the control
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<ControlAlert>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ControlAlert {
+    pub id: String,
+    pub details: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// Percentage of passed controls, rounded to one decimal
+fn score(passed: usize, failed: usize) -> f64 {
+    match passed + failed {
+        0 => 100.0,
+        total => (passed as f64 * 1000.0 / total as f64).round() / 10.0,
+    }
+}
+
+impl Report {
+    pub fn new(benchmark: Benchmark, rules: &Rules, base: &Path) -> Self {
+        let mut covered: HashMap<String, Vec<String>> = HashMap::new();
+        let mut rule_names = HashMap::new();
+        for (name, metadata) in rules.rule_metadata() {
+            let id = metadata.id.to_string();
+            if !rules.is_enabled(name, &id) {
+                continue;
+            }
+            rule_names.insert(id.clone(), name.to_string());
+            for control in &metadata.cis {
+                covered.entry(control.clone()).or_default().push(id.clone());
+            }
+        }
+
+        Report {
+            benchmark: benchmark.name().to_string(),
+            score: 100.0,
+            covered,
+            rules: rule_names,
+            controls: benchmark.controls(),
+            base: base.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Add the services of a compose file, with the alerts of the file and
+    /// the errors of the rules run on it
+    pub fn add(&mut self, compose_file: &ComposeFile, alerts: &[Alert], errors: &[Diagnostic]) {
+        let files: Vec<String> = compose_file
+            .files
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&self.base)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        let errored: Vec<&str> = errors.iter().filter_map(|e| e.rule.as_deref()).collect();
+
+        let mut services: Vec<&String> = compose_file.compose.services.keys().collect();
+        services.sort();
+
+        for service in services {
+            let alerts: Vec<&Alert> = alerts
+                .iter()
+                .filter(|a| a.service.as_ref() == Some(service) && a.suppression.is_none())
+                .collect();
+
+            let controls: Vec<ControlResult> = self
+                .controls
+                .iter()
+                .map(|(id, title)| {
+                    let ids = self.covered.get(*id);
+                    let failing: Vec<ControlAlert> = alerts
+                        .iter()
+                        .filter(|alert| {
+                            ids.map(|ids| ids.contains(&alert.id.to_string()))
+                                .unwrap_or(false)
+                        })
+                        .map(|alert| ControlAlert {
+                            id: alert.id.to_string(),
+                            details: alert.details.clone(),
+                            key: alert.path.key.clone(),
+                        })
+                        .collect();
+
+                    let failed_rule = ids
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|id| self.rules.get(id))
+                        .any(|rule| errored.contains(&rule.as_str()));
+                    let status = match (ids, failing.is_empty()) {
+                        (None, _) => Status::NotApplicable,
+                        (Some(_), false) => Status::Fail,
+                        (Some(_), true) if failed_rule => Status::NotEvaluated,
+                        (Some(_), true) => Status::Pass,
+                    };
+                    ControlResult {
+                        id: id.to_string(),
+                        title: title.to_string(),
+                        status,
+                        alerts: failing,
+                    }
+                })
+                .collect();
+
+            let count = |status: Status| controls.iter().filter(|c| c.status == status).count();
+            let (passed, failed) = (count(Status::Pass), count(Status::Fail));
+            self.passed += passed;
+            self.failed += failed;
+            self.not_applicable += count(Status::NotApplicable);
+            self.not_evaluated += count(Status::NotEvaluated);
+
+            self.services.push(ServiceReport {
+                files: files.clone(),
+                service: service.clone(),
+                score: score(passed, failed),
+                controls,
+            });
+        }
+        self.score = score(self.passed, self.failed);
+    }
+
+    /// Add the files that failed to load and the rules that errored
+    pub fn add_errors(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            let path = &diagnostic.path.path;
+            self.errors.push(ReportError {
+                message: diagnostic.message.clone(),
+                path: match path.as_os_str().is_empty() {
+                    true => None,
+                    false => Some(
+                        path.strip_prefix(&self.base)
+                            .unwrap_or(path)
+                            .display()
+                            .to_string(),
+                    ),
+                },
+                line: diagnostic.path.line().map(|line| line + 1),
+                rule: diagnostic.rule.clone(),
+            });
+        }
+    }
+
+    pub fn write(&self, path: &PathBuf) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, &self)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportError {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Line of the problem, starting at 1
+    pub line: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, rules::tests::compose_file, security::AlertLocation};
+
+    #[test]
+    fn cis_docker() {
+        let (_dir, compose_file) = compose_file("services:\n  # quibble-ignore: HEALTHCHECK -- checked by the load balancer\n  web:\n    image: nginx:1.27\n    privileged: true\n    read_only: true\n    ports:\n      - 127.0.0.1:8080:80\n  db:\n    image: postgres:16\n    security_opt:\n      - no-new-privileges:true\n",
+        );
+        let mut rules = Rules::new(Config::default());
+        rules.enable_benchmark();
+        let alerts = rules.run(&compose_file);
+
+        let mut report = Report::new(Benchmark::CisDocker, &rules, Path::new("/"));
+        report.add(&compose_file, &alerts, &[]);
+
+        assert_eq!(report.services.len(), 2);
+        let web = &report.services[1];
+        assert_eq!(web.service, "web");
+        let status = |id: &str| {
+            web.controls
+                .iter()
+                .find(|c| c.id == id)
+                .map(|c| c.status)
+                .unwrap()
+        };
+        assert_eq!(status("5.5"), Status::Fail);
+        assert_eq!(status("5.13"), Status::Pass);
+        assert_eq!(status("5.14"), Status::Pass);
+        assert_eq!(status("5.26"), Status::Fail);
+        // Suppressed alerts do not fail the control
+        assert_eq!(status("5.27"), Status::Pass);
+        assert_eq!(status("5.7"), Status::NotApplicable);
+
+        let failed = web.controls.iter().find(|c| c.id == "5.5").unwrap();
+        assert_eq!(failed.alerts[0].id, "PRIVILEGED_CONTAINER");
+        assert_eq!(
+            report.passed + report.failed + report.not_applicable + report.not_evaluated,
+            2 * CIS_DOCKER.len()
+        );
+        assert!(report.score > 0.0 && report.score < 100.0);
+
+        report.add_errors(&[Diagnostic {
+            message: String::from("Rule `failing` failed"),
+            path: AlertLocation {
+                path: PathBuf::from("/srv/compose.yml"),
+                ..Default::default()
+            },
+            rule: Some(String::from("failing")),
+        }]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["errors"][0]["path"], "srv/compose.yml");
+        assert_eq!(json["errors"][0]["rule"], "failing");
+    }
+
+    #[test]
+    fn cis_docker_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("compose.yml"),
+            "services:\n  web:\n    image: nginx:1.27\n    read_only: true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("compose.prod.yml"),
+            "services:\n  web:\n    privileged: true\n",
+        )
+        .unwrap();
+        let files = [
+            dir.path().join("compose.yml"),
+            dir.path().join("compose.prod.yml"),
+        ];
+        let compose_file = ComposeFile::load(&files, &Default::default()).unwrap();
+        let mut rules = Rules::new(Config::default());
+        rules.enable_benchmark();
+        let alerts = rules.run(&compose_file);
+
+        // Controls of a rule that errored are not evaluated
+        let errors = [Diagnostic {
+            message: String::from("Rule `read_only_filesystem` failed"),
+            rule: Some(String::from("read_only_filesystem")),
+            ..Default::default()
+        }];
+        let mut report = Report::new(Benchmark::CisDocker, &rules, dir.path());
+        report.add(&compose_file, &alerts, &errors);
+
+        // Rows name every file of the project
+        let web = &report.services[0];
+        assert_eq!(web.files, vec!["compose.yml", "compose.prod.yml"]);
+        let status = |id: &str| web.controls.iter().find(|c| c.id == id).unwrap().status;
+        assert_eq!(status("5.13"), Status::NotEvaluated);
+        assert_eq!(status("5.5"), Status::Fail);
+        assert_eq!(report.not_evaluated, 1);
+    }
+}
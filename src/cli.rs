@@ -1,7 +1,9 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::ExitCode};
 
 use clap::{Parser, Subcommand};
 
+use crate::{benchmark::Benchmark, config::ParseErrorPolicy};
+
 pub const VERSION_NUMBER: &str = env!("CARGO_PKG_VERSION");
 pub const AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 
@@ -12,6 +14,25 @@ pub const BANNER: &str = r#" _____       _ _     _     _
 \ \/' / |_| | | |_) | |_) | |  __/
  \_/\_\\__,_|_|_.__/|_.__/|_|\___|"#;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Exit codes, so CI can tell a failing scan from a broken one
+pub enum ExitStatus {
+    /// No findings at or above the severity threshold
+    Success = 0,
+    /// Findings at or above the severity threshold
+    Findings = 1,
+    /// Invalid arguments or config (the same code as argument parsing)
+    Usage = 2,
+    /// Files or rules that could not be checked
+    ScanError = 3,
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Arguments {
@@ -32,6 +53,7 @@ pub struct Arguments {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum ArgumentCommands {
     /// Check if setup and tools are all available
     Check,
@@ -41,6 +63,44 @@ pub enum ArgumentCommands {
         #[clap(short, long, default_value = "./")]
         path: PathBuf,
 
+        /// Compose file(s) merged in order into a single project
+        /// (base file first, then overrides)
+        #[clap(long = "file")]
+        files: Vec<PathBuf>,
+
+        /// Env file(s) used for variable interpolation (replaces the
+        /// project `.env` file)
+        #[clap(long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Use the process environment for variable interpolation
+        #[clap(long, default_value_t = false)]
+        process_env: bool,
+
+        /// Only scan the services of these compose profile(s), services
+        /// without profiles and dependencies are always scanned. Every
+        /// service is scanned by default
+        #[clap(long = "profile")]
+        profiles: Vec<String>,
+
+        /// Scan the services of every profile, even when profiles are
+        /// selected with `--profile` or `COMPOSE_PROFILES`
+        #[clap(long, default_value_t = false)]
+        all_profiles: bool,
+
+        /// Glob(s) of files and directories to skip when searching for
+        /// compose files
+        #[clap(long)]
+        exclude: Vec<String>,
+
+        /// Maximum directory depth to search for compose files
+        #[clap(long)]
+        max_depth: Option<usize>,
+
+        /// Follow symbolic links when searching for compose files
+        #[clap(long, default_value_t = false)]
+        follow_symlinks: bool,
+
         /// Output Location
         #[clap(short, long)]
         output: Option<PathBuf>,
@@ -57,10 +117,50 @@ pub enum ArgumentCommands {
         #[clap(short, long)]
         filter: Option<String>,
 
+        /// Rule name(s) or alert ID(s) to enable (overrides the config)
+        #[clap(long = "enable-rule")]
+        enable_rules: Vec<String>,
+
+        /// Rule name(s) or alert ID(s) to disable (overrides the config)
+        #[clap(long = "disable-rule")]
+        disable_rules: Vec<String>,
+
+        /// Baseline file, findings in the baseline are not reported
+        #[clap(long)]
+        baseline: Option<PathBuf>,
+
+        /// Write the findings to a baseline file, instead of reporting them
+        #[clap(long)]
+        write_baseline: Option<PathBuf>,
+
+        /// Apply the suggested fixes of the reported alerts to the compose
+        /// files
+        #[clap(long, default_value_t = false)]
+        fix: bool,
+
+        /// Print the fixes as a unified diff, instead of writing them
+        #[clap(long, default_value_t = false, requires = "fix")]
+        dry_run: bool,
+
+        /// Report the compliance of every service with a benchmark, instead
+        /// of the alerts
+        #[clap(long, value_enum, conflicts_with_all = ["fix", "baseline", "write_baseline"])]
+        benchmark: Option<Benchmark>,
+
+        /// Policy for compose files that fail to parse (overrides the config)
+        #[clap(long, value_enum)]
+        parse_errors: Option<ParseErrorPolicy>,
+
         /// Disable / Enabled CLI failure
         #[clap(long, default_value_t = false)]
         disable_fail: bool,
     },
+    /// List the rules and the alerts they report
+    Rules {
+        /// Output Format (`cli` or `markdown`)
+        #[clap(long, default_value_t=String::from("cli"))]
+        format: String,
+    },
     /// Scan registry containers
     Registry {
         /// Domain of the registry wanting to scan
@@ -1,69 +1,162 @@
 use anyhow::{anyhow, Result};
-use log::{debug, warn};
+use log::{debug, info, warn};
 use std::{
     collections::HashMap,
     fmt::Display,
-    fs::OpenOptions,
-    io::Read,
     path::{Path, PathBuf},
 };
 
+pub mod discovery;
+pub mod document;
+pub mod dotenv;
+pub mod edit;
+pub mod extends;
+pub mod include;
+pub mod interpolation;
+pub mod merge;
+pub mod ports;
+pub mod project;
+pub mod schema;
 pub mod spec;
+pub mod suppressions;
+pub mod volumes;
+pub mod yaml;
 
-pub use crate::rules::*;
+pub use ports::*;
 pub use spec::*;
-use walkdir::WalkDir;
+pub use volumes::*;
+
+use crate::{
+    compose::{
+        discovery::DiscoveryOptions,
+        document::Document,
+        edit::Change,
+        interpolation::{Interpolator, UnresolvedVariable},
+        schema::SchemaViolation,
+        suppressions::IgnoreComment,
+        ComposeSpec,
+    },
+    security::{Alert, AlertLocation, Anchor, Diagnostic, Edit, Fix, Region},
+};
+use thiserror::Error;
 
-use crate::compose::ComposeSpec;
+#[derive(Debug, Error)]
+#[error("{message}")]
+/// Compose file that could not be read or parsed
+pub struct LoadError {
+    /// File with the error
+    pub path: PathBuf,
+    /// Region of the error, when it is known
+    pub region: Option<Region>,
+    pub message: String,
+}
+
+impl LoadError {
+    pub fn new(path: &Path, region: Option<Region>, message: impl ToString) -> Self {
+        LoadError {
+            path: path.to_path_buf(),
+            region,
+            message: message.to_string(),
+        }
+    }
+
+    /// Diagnostic for an error loading `files`, pointing at the file (and
+    /// region) of the `LoadError` that caused it
+    pub fn diagnostic(files: &[PathBuf], err: &anyhow::Error) -> Diagnostic {
+        match err.chain().find_map(|e| e.downcast_ref::<LoadError>()) {
+            Some(load) => Diagnostic {
+                message: load.message.clone(),
+                path: AlertLocation {
+                    path: load.path.clone(),
+                    region: load.region,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            None => Diagnostic {
+                message: format!("{err:#}"),
+                path: AlertLocation {
+                    path: files.first().cloned().unwrap_or_default(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Options used when loading compose files
+pub struct LoadOptions {
+    /// Env files used for interpolation instead of the project `.env`
+    pub env_files: Vec<PathBuf>,
+    /// Use the process environment for interpolation
+    pub process_env: bool,
+    /// Selected compose profiles, every service is loaded when none are
+    pub profiles: Vec<String>,
+    /// Load every service, even when profiles are selected
+    pub all_profiles: bool,
+}
 
 pub struct ComposeFile {
     /// Path to the file
     pub path: PathBuf,
+    /// All files merged into this compose project, in order
+    pub files: Vec<PathBuf>,
     /// Compose Spec
     pub compose: ComposeSpec,
-    /// Key line mappings
-    pub mappings: HashMap<String, i32>,
+    /// Raw compose document, interpolated and merged
+    pub raw: serde_yaml::Value,
+    /// Source regions of the compose keys
+    pub mappings: HashMap<String, Region>,
+    /// Anchors that keys were pulled in from with an alias or merge key
+    pub anchors: HashMap<String, Anchor>,
+    /// File that set a key, when it is not `path`
+    pub sources: HashMap<String, PathBuf>,
+    /// Raw values of keys that were changed by interpolation
+    pub interpolated: HashMap<String, String>,
+    /// Variables that could not be resolved during interpolation
+    pub unresolved: Vec<UnresolvedVariable>,
+    /// Parts of the project that do not match the compose-spec schema
+    pub violations: Vec<SchemaViolation>,
+    /// `quibble-ignore` comments of every file in the project
+    pub ignores: HashMap<PathBuf, Vec<IgnoreComment>>,
+    /// Source of every file in the project, used to build fixes
+    pub contents: HashMap<PathBuf, String>,
 }
 
-pub fn find(path: &Path) -> Result<Vec<ComposeFile>> {
-    let mut compose_files: Vec<ComposeFile> = Vec::new();
-
-    if path.is_file() {
+/// Find and load the compose projects at a path (a compose file or a
+/// directory). Files that fail to load are added to `diagnostics`.
+pub fn find(
+    path: &Path,
+    options: &LoadOptions,
+    discovery: &DiscoveryOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<ComposeFile>> {
+    let projects = if path.is_file() {
         debug!("Path is a file, parsing compose file");
-
-        match ComposeFile::parse(path) {
-            Ok(c) => compose_files.push(c),
-            Err(err) => return Err(err),
-        }
+        vec![vec![path.to_path_buf()]]
     } else if path.is_dir() {
-        for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
-            let subpath = &entry.into_path();
-            // TODO: This check should be better
+        project::group(discovery::discover(path, discovery)?)
+    } else {
+        return Err(anyhow!("Unknown path type..."));
+    };
 
-            let filter = match subpath.extension() {
-                Some(p) => p == "yml" || p == "yaml",
-                None => false,
-            };
+    let mut compose_files: Vec<ComposeFile> = Vec::new();
 
-            if subpath.is_file() && filter {
-                debug!("Compose file: {:?}", subpath);
-
-                match ComposeFile::parse(subpath) {
-                    Ok(c) => {
-                        compose_files.push(c);
-                    }
-                    Err(err) => {
-                        debug!("Docker Compose file unable to parse: {:?}", subpath);
-                        debug!("Error processing: {}", err);
-                    }
-                }
+    for files in projects {
+        debug!("Compose file(s): {:?}", files);
+
+        match ComposeFile::load(&files, options) {
+            Ok(c) => compose_files.push(c),
+            Err(err) => {
+                debug!("Docker Compose file unable to parse: {:?}", files);
+                diagnostics.push(LoadError::diagnostic(&files, &err));
             }
         }
-    } else {
-        return Err(anyhow!("Unknown path type..."));
     }
 
-    if compose_files.is_empty() {
+    if compose_files.is_empty() && diagnostics.is_empty() {
         warn!("No compose file were found: {}", path.display());
     }
 
@@ -72,132 +165,369 @@ pub fn find(path: &Path) -> Result<Vec<ComposeFile>> {
 
 impl Display for ComposeFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ComposeFile('{}')", self.path.display())
+        let files: Vec<String> = self
+            .files
+            .iter()
+            .map(|f| format!("'{}'", f.display()))
+            .collect();
+        write!(f, "ComposeFile({})", files.join(", "))
     }
 }
 
 impl ComposeFile {
-    pub fn parse(path: &Path) -> Result<Self> {
-        let mut file = OpenOptions::new().read(true).open(path)?;
-        let mut data = String::new();
-        file.read_to_string(&mut data)?;
-
-        let mappings = ComposeFile::mappings(&data)?;
+    /// Load a compose project made of one or more files, merging each file
+    /// on top of the previous ones
+    pub fn load(files: &[PathBuf], options: &LoadOptions) -> Result<Self> {
+        let document = ComposeFile::load_documents(files, options, &mut Vec::new())?;
+        let violations = schema::validate(&document.raw);
+        let raw = document.raw.clone();
+        let mut compose: ComposeSpec =
+            serde_path_to_error::deserialize(document.raw).map_err(|err| {
+                let key = err.path().to_string();
+                let (path, region) = match closest(&document.mappings, &key) {
+                    Some(key) => (
+                        document.sources.get(key).unwrap_or(&document.path),
+                        document.mappings.get(key).copied(),
+                    ),
+                    None => (&document.path, None),
+                };
+                LoadError::new(path, region, format!("{key}: {}", err.inner()))
+            })?;
+
+        // Every service is scanned unless profiles are selected
+        if !options.all_profiles && !options.profiles.is_empty() {
+            let enabled = compose.enabled_services(&options.profiles);
+            let mut skipped: Vec<&String> = compose
+                .services
+                .keys()
+                .filter(|name| !enabled.contains(*name))
+                .collect();
+            if !skipped.is_empty() {
+                skipped.sort();
+                let skipped: Vec<String> = skipped.iter().map(|name| format!("`{name}`")).collect();
+                info!(
+                    "Skipping service(s) not in the selected profiles: {}",
+                    skipped.join(", ")
+                );
+            }
+            compose.services.retain(|name, _| enabled.contains(name));
+        }
 
-        let cs: ComposeSpec = serde_yaml::from_str(data.as_str())?;
+        let mut compose_file = ComposeFile {
+            path: document.path,
+            files: files.to_vec(),
+            compose,
+            raw,
+            mappings: document.mappings,
+            anchors: document.anchors,
+            sources: document.sources,
+            interpolated: document.interpolated,
+            unresolved: document.unresolved,
+            violations: Vec::new(),
+            ignores: HashMap::new(),
+            contents: HashMap::new(),
+        };
+        // Drop violations of services that are not enabled
+        compose_file.violations = violations
+            .into_iter()
+            .filter(|v| !v.key.starts_with("services.") || compose_file.service(&v.key).is_some())
+            .collect();
+
+        // Files pulled in by `include` and `extends` are only known by the
+        // keys they set, env files can have `quibble-ignore` comments too
+        let env_files: Vec<PathBuf> = compose_file
+            .env_files()
+            .into_iter()
+            .map(|env_file| env_file.path)
+            .filter(|path| path.is_file())
+            .collect();
+        let mut paths: Vec<&PathBuf> = compose_file.sources.values().collect();
+        paths.extend(files);
+        paths.extend(&env_files);
+        for path in paths {
+            if compose_file.ignores.contains_key(path) {
+                continue;
+            }
+            let data = std::fs::read_to_string(path).unwrap_or_default();
+            compose_file
+                .ignores
+                .insert(path.clone(), suppressions::parse(&data));
+            compose_file.contents.insert(path.clone(), data);
+        }
 
-        Ok(ComposeFile {
-            path: path.to_path_buf(),
-            compose: cs,
-            mappings,
-        })
+        Ok(compose_file)
     }
 
-    /// Generate mappings for the compose file keys. This allows us to
-    /// point to a specific line in the file when we find a rule violation.
-    pub fn mappings(data: &str) -> Result<HashMap<String, i32>> {
-        let mut mappings: HashMap<String, i32> = HashMap::new();
-        let mut stack: Vec<&str> = Vec::new();
-        let mut current_index = 0;
+    /// Read, resolve (`include` and `extends`) and merge the files of a
+    /// project. `stack` holds the files currently being loaded.
+    pub fn load_documents(
+        files: &[PathBuf],
+        options: &LoadOptions,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<Document> {
+        let first = files
+            .first()
+            .ok_or_else(|| anyhow!("No compose files to load"))?;
+
+        let directory = first.parent().unwrap_or(Path::new("."));
+        let interpolator = Interpolator::for_project(directory, options)?;
+
+        let mut project: Option<Document> = None;
+
+        for path in files {
+            let canonical = document::canonical(path);
+            if stack.contains(&canonical) {
+                return Err(anyhow!("Circular `include` of {}", path.display()));
+            }
+            stack.push(canonical);
 
-        for (number, line) in data.split('\n').enumerate() {
-            let number = number as i32;
-            let index = line.matches("  ").count();
-            let line = line.trim();
+            let mut document = Document::read(path, &interpolator)?;
+            include::resolve(&mut document, options, stack)?;
+            extends::resolve(&mut document, &interpolator)?;
 
-            // skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
+            stack.pop();
 
-            if current_index > index {
-                let diff = current_index - index;
-                for _ in 0..diff {
-                    stack.pop();
-                }
-                current_index = index;
+            match project.as_mut() {
+                Some(project) => project.merge(document),
+                None => project = Some(document),
             }
+        }
 
-            // key with no value
-            if line.ends_with(':') {
-                let key = line.trim_end_matches(':');
+        let mut project = project.ok_or_else(|| anyhow!("No compose files to load"))?;
+        project.raw = merge::strip_tags(project.raw);
+        Ok(project)
+    }
 
-                let full_key = stack.join(".");
-                if !full_key.is_empty() {
-                    mappings.insert(format!("{}.{}", full_key, key), number);
-                } else {
-                    mappings.insert(key.to_string(), number);
-                }
+    /// Location (file and region) of a compose key, such as
+    /// `services.web.privileged`. Keys that are not in the source (merged
+    /// or defaulted values) fall back to their closest parent key.
+    pub fn location(&self, key: &str) -> AlertLocation {
+        match closest(&self.mappings, key) {
+            Some(current) => AlertLocation {
+                path: self.sources.get(current).unwrap_or(&self.path).clone(),
+                region: self.mappings.get(current).copied(),
+                anchor: self.anchors.get(current).cloned(),
+                profiles: self.profiles(key),
+                key: Some(key.to_string()),
+            },
+            None => AlertLocation {
+                path: self.sources.get(key).unwrap_or(&self.path).clone(),
+                region: None,
+                anchor: None,
+                profiles: self.profiles(key),
+                key: Some(key.to_string()),
+            },
+        }
+    }
 
-                stack.push(key);
-                current_index = index;
-                continue;
-            }
+    /// Fix for an alert that makes a change to a compose key, `None` when
+    /// the change can not be made in place (the value comes from an anchor
+    /// or interpolation, the YAML is in flow style, ...)
+    pub fn fix(&self, key: &str, description: impl ToString, change: Change) -> Option<Fix> {
+        if self.interpolated.contains_key(key) {
+            return None;
+        }
+        let current = closest(&self.mappings, key)?;
+        // Missing keys can only be appended to, at their parent
+        let parent = key.rsplit_once('.').map(|(parent, _)| parent);
+        let editable =
+            current == key || (matches!(change, Change::Append(_)) && parent == Some(current));
+        if !editable || self.anchors.contains_key(current) {
+            return None;
+        }
 
-            // arrays
-            if line.starts_with('-') {
-                let mut full_key = stack.join(".");
-                let array_key = format!("[{}]", line.trim_start_matches("- "));
+        let path = self.sources.get(current).unwrap_or(&self.path);
+        let data = self.contents.get(path)?;
+        let value = self.scalar(key);
+        let (region, text) = edit::edit(
+            data,
+            key,
+            *self.mappings.get(current)?,
+            value.as_deref(),
+            &change,
+        )?;
+
+        Some(Fix {
+            description: description.to_string(),
+            edits: vec![Edit {
+                path: path.clone(),
+                region,
+                text,
+            }],
+        })
+    }
 
-                full_key.push_str(array_key.as_str());
+    /// Scalar value of a compose key in the loaded project. Service names
+    /// can contain `.` so the longest matching mapping key is used.
+    fn scalar(&self, key: &str) -> Option<String> {
+        let mut value = &self.raw;
+        let mut rest = key;
+        while !rest.is_empty() {
+            value = match value {
+                serde_yaml::Value::Mapping(mapping) => {
+                    let (name, value) = mapping
+                        .iter()
+                        .filter_map(|(name, value)| Some((name.as_str()?, value)))
+                        .filter(|(name, _)| {
+                            rest.strip_prefix(name)
+                                .map(|r| r.is_empty() || r.starts_with(['.', '[']))
+                                .unwrap_or(false)
+                        })
+                        .max_by_key(|(name, _)| name.len())?;
+                    rest = &rest[name.len()..];
+                    value
+                }
+                serde_yaml::Value::Sequence(items) => {
+                    let (index, remaining) = rest.strip_prefix('[')?.split_once(']')?;
+                    rest = remaining;
+                    items.get(index.parse::<usize>().ok()?)?
+                }
+                _ => return None,
+            };
+            rest = rest.strip_prefix('.').unwrap_or(rest);
+        }
+        match value {
+            serde_yaml::Value::String(value) => Some(value.clone()),
+            serde_yaml::Value::Bool(value) => Some(value.to_string()),
+            serde_yaml::Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
 
-                mappings.insert(full_key, number);
-                current_index = index;
-                continue;
-            }
+    /// `quibble-ignore` comment that suppresses an alert of `rule`, on the
+    /// alert line, the line of the anchor alias or for the whole file
+    pub fn suppression(&self, rule: &str, alert: &Alert) -> Option<&IgnoreComment> {
+        let alias = alert.path.anchor.as_ref().map(|a| a.alias.start_line);
+        self.ignores.get(&alert.path.path)?.iter().find(|comment| {
+            let id = alert.id.to_string();
+            (comment.matches(&id) || comment.matches(rule))
+                && match comment.line {
+                    Some(line) => Some(line) == alert.path.line() || Some(line) == alias,
+                    None => true,
+                }
+        })
+    }
 
-            // key with value
-            let key = line.split_once(':');
-            if let Some((key, _)) = key {
-                let mut full_key = stack.join(".");
-                if !full_key.is_empty() {
-                    full_key.push('.');
+    /// `env_file` entries of the services, resolved relative to the compose
+    /// file that set them
+    pub fn env_files(&self) -> Vec<ServiceEnvFile> {
+        let mut env_files = Vec::new();
+        for (name, service) in &self.compose.services {
+            let entries: Vec<(String, &String, bool)> = match &service.env_file {
+                Some(EnvFiles::Str(path)) => {
+                    vec![(format!("services.{name}.env_file"), path, true)]
                 }
-                full_key.push_str(key);
+                Some(EnvFiles::List(list)) => list
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        let key = format!("services.{name}.env_file[{index}]");
+                        match entry {
+                            EnvFile::Str(path) => (key, path, true),
+                            EnvFile::Long { path, required, .. } => {
+                                (format!("{key}.path"), path, required.unwrap_or(true))
+                            }
+                        }
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
 
-                // println!("Key :: {}:{}", full_key, number);
-                mappings.insert(full_key, number);
-                current_index = index;
+            for (key, path, required) in entries {
+                let location = self.location(&key);
+                let directory = location.path.parent().unwrap_or(Path::new("."));
+                env_files.push(ServiceEnvFile {
+                    service: name.clone(),
+                    path: directory.join(path),
+                    required,
+                    location,
+                });
             }
         }
+        env_files.sort_by(|a, b| (&a.path, &a.service).cmp(&(&b.path, &b.service)));
+        env_files
+    }
+
+    /// Profiles of the service a compose key belongs to
+    pub fn profiles(&self, key: &str) -> Vec<String> {
+        self.service(key)
+            .and_then(|(_, service)| service.profiles.clone())
+            .unwrap_or_default()
+    }
 
-        Ok(mappings)
+    /// Name and definition of the service a compose key belongs to
+    pub fn service(&self, key: &str) -> Option<(&String, &Service)> {
+        let key = key.strip_prefix("services.")?;
+        // Service names can contain `.` so use the longest matching name
+        self.compose
+            .services
+            .iter()
+            .filter(|(name, _)| {
+                key.strip_prefix(name.as_str())
+                    .map(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+                    .unwrap_or(false)
+            })
+            .max_by_key(|(name, _)| name.len())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// `env_file` entry of a service
+pub struct ServiceEnvFile {
+    pub service: String,
+    /// Path of the env file, relative paths are resolved against the compose
+    /// file that set it
+    pub path: PathBuf,
+    /// Compose fails to start the service when a required file is missing
+    pub required: bool,
+    /// Location of the `env_file` entry
+    pub location: AlertLocation,
+}
+
+/// Closest key to `key` (itself or a parent key) that has a region
+pub fn closest<'a>(mappings: &HashMap<String, Region>, key: &'a str) -> Option<&'a str> {
+    let mut current = key;
+    loop {
+        if mappings.contains_key(current) {
+            return Some(current);
+        }
+        let index = current.rfind(['.', '['])?;
+        current = &current[..index];
     }
 }
 
 #[cfg(test)]
 mod test {
+    fn lines(data: &str) -> std::collections::HashMap<String, i32> {
+        super::yaml::mappings(data)
+            .unwrap()
+            .regions
+            .into_iter()
+            .map(|(key, region)| (key, region.start_line))
+            .collect()
+    }
+
     #[test]
     fn mappings() {
-        let data = String::from(
-            "version: \"3.9\"\n\nservices:\n  web:\n    image: nginx:latest\n    ports:\n      - \"80:80\"\n"
+        let mappings = lines(
+            "version: \"3.9\"\n\nservices:\n  web:\n    image: nginx:latest\n    ports:\n      - \"80:80\"\n",
         );
 
-        let mappings = super::ComposeFile::mappings(&data).unwrap();
-
         assert_eq!(mappings.len(), 6);
         assert_eq!(mappings.get("version").unwrap_or(&1), &0);
         assert_eq!(mappings.get("services").unwrap_or(&1), &2);
         assert_eq!(mappings.get("services.web").unwrap_or(&1), &3);
         assert_eq!(mappings.get("services.web.image").unwrap_or(&1), &4);
         assert_eq!(mappings.get("services.web.ports").unwrap_or(&1), &5);
-        assert_eq!(
-            mappings.get("services.web.ports[\"80:80\"]").unwrap_or(&1),
-            &6
-        );
+        assert_eq!(mappings.get("services.web.ports[0]").unwrap_or(&1), &6);
     }
 
     #[test]
     fn mappings2() {
-        let data = String::from(
+        let mappings = lines(
             "version: \"3\"\nnetworks:\n  backend:\n    external: true\nservices:\n  web:\n    image: nginx:latest\n",
         );
 
-        let mappings = super::ComposeFile::mappings(&data).unwrap();
-        for (map, num) in mappings.iter() {
-            println!("{}: {}", map, num);
-        }
-
         assert_eq!(mappings.len(), 7);
         assert_eq!(mappings.get("version").unwrap_or(&1), &0);
 
@@ -209,4 +539,379 @@ mod test {
         assert_eq!(mappings.get("services.web").unwrap_or(&1), &5);
         assert_eq!(mappings.get("services.web.image").unwrap_or(&1), &6);
     }
+
+    #[test]
+    fn load_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("compose.yml");
+        let overrides = dir.path().join("compose.override.yml");
+        std::fs::write(&base, "services:\n  web:\n    image: nginx\n").unwrap();
+        std::fs::write(&overrides, "services:\n  web:\n    privileged: true\n").unwrap();
+
+        let project = super::ComposeFile::load(
+            &[base.clone(), overrides.clone()],
+            &super::LoadOptions::default(),
+        )
+        .unwrap();
+        let web = project.compose.services.get("web").unwrap();
+
+        assert_eq!(web.privileged, Some(true));
+        assert_eq!(web.image.as_deref(), Some("nginx"));
+
+        let location = project.location("services.web.privileged");
+        assert_eq!(location.path, overrides);
+        assert_eq!(location.line(), Some(2));
+        assert_eq!(project.location("services.web").path, base);
+    }
+
+    #[test]
+    fn load_project_sequences() {
+        let temp = write(&[
+            (
+                "compose.yml",
+                "services:\n  web:\n    image: nginx\n    ports:\n      - \"8080:80\"\n    volumes:\n      - ./a:/data\n      - logs:/logs\n    command: [run, a, b]\n",
+            ),
+            (
+                "compose.override.yml",
+                "services:\n  web:\n    ports:\n      - \"8080:80\"\n      - \"127.0.0.1:443:443\"\n    volumes:\n      - ./b:/data\n    command: [serve]\n",
+            ),
+        ]);
+        let dir = temp.path();
+        let (base, overrides) = (dir.join("compose.yml"), dir.join("compose.override.yml"));
+        let project = super::ComposeFile::load(
+            &[base.clone(), overrides.clone()],
+            &super::LoadOptions::default(),
+        )
+        .unwrap();
+        let location = |key: &str| {
+            let location = project.location(key);
+            (location.path.clone(), location.line())
+        };
+
+        // Duplicates keep the base item, new items are appended
+        assert_eq!(location("services.web.ports[0]"), (base.clone(), Some(4)));
+        assert_eq!(
+            location("services.web.ports[1]"),
+            (overrides.clone(), Some(4))
+        );
+        // Items with the same target are replaced in place
+        assert_eq!(
+            location("services.web.volumes[0]"),
+            (overrides.clone(), Some(6))
+        );
+        assert_eq!(location("services.web.volumes[1]"), (base.clone(), Some(7)));
+        // Replaced sequences only keep the override items
+        assert_eq!(
+            location("services.web.command[0]"),
+            (overrides.clone(), Some(7))
+        );
+        assert_eq!(
+            location("services.web.command[2]"),
+            (overrides.clone(), Some(7))
+        );
+    }
+
+    /// Write `files` to a temporary directory, which is removed when the
+    /// returned `TempDir` is dropped
+    fn write(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, data) in files {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn load_extends() {
+        let temp = write(&[
+            (
+                "compose.yml",
+                "services:\n  web:\n    extends:\n      file: common.yml\n      service: base\n    image: nginx\n    cap_add:\n      - SYS_ADMIN\n  worker:\n    extends: web\n",
+            ),
+            (
+                "common.yml",
+                "services:\n  base:\n    privileged: true\n    image: busybox\n    cap_add: [NET_ADMIN]\n    user: ${BASE_USER}\n    command: run ${QUIBBLE_EXTENDS_MISSING}\n",
+            ),
+        ]);
+        let dir = temp.path();
+
+        let project =
+            super::ComposeFile::load(&[dir.join("compose.yml")], &super::LoadOptions::default())
+                .unwrap();
+        let web = project.compose.services.get("web").unwrap();
+        let worker = project.compose.services.get("worker").unwrap();
+
+        assert_eq!(web.privileged, Some(true));
+        assert_eq!(web.image.as_deref(), Some("nginx"));
+        assert_eq!(worker.privileged, Some(true));
+        assert_eq!(worker.image.as_deref(), Some("nginx"));
+
+        let location = project.location("services.web.privileged");
+        assert_eq!(location.path, dir.join("common.yml"));
+        assert_eq!(location.line(), Some(2));
+        assert_eq!(
+            project.location("services.web.image").path,
+            dir.join("compose.yml")
+        );
+        assert_eq!(
+            project.location("services.worker.privileged").path,
+            dir.join("common.yml")
+        );
+        assert_eq!(
+            project.location("services.web").path,
+            dir.join("compose.yml")
+        );
+
+        // Items of the extending service come after the items of the base
+        let location = project.location("services.web.cap_add[1]");
+        assert_eq!(location.path, dir.join("compose.yml"));
+        assert_eq!(location.line(), Some(7));
+        assert_eq!(
+            project.location("services.web.cap_add[0]").path,
+            dir.join("common.yml")
+        );
+
+        // Interpolation of the base file follows the extending services
+        assert_eq!(
+            project
+                .interpolated
+                .get("services.worker.user")
+                .map(String::as_str),
+            Some("${BASE_USER}")
+        );
+        let unresolved: Vec<&str> = project
+            .unresolved
+            .iter()
+            .filter(|v| v.variable == "QUIBBLE_EXTENDS_MISSING")
+            .map(|v| v.key.as_str())
+            .collect();
+        assert_eq!(unresolved.len(), 2);
+        assert!(unresolved.contains(&"services.web.command"));
+        assert!(unresolved.contains(&"services.worker.command"));
+    }
+
+    #[test]
+    fn load_extends_cycle() {
+        let temp = write(&[(
+            "compose.yml",
+            "services:\n  a:\n    extends: b\n  b:\n    extends: a\n",
+        )]);
+        let dir = temp.path();
+
+        let result =
+            super::ComposeFile::load(&[dir.join("compose.yml")], &super::LoadOptions::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn load_include() {
+        let temp = write(&[
+            (
+                "compose.yml",
+                "include:\n  - db/compose.yml\nservices:\n  web:\n    image: nginx\n",
+            ),
+            (
+                "db/compose.yml",
+                "services:\n  db:\n    image: postgres\n    privileged: true\n",
+            ),
+        ]);
+        let dir = temp.path();
+
+        let project =
+            super::ComposeFile::load(&[dir.join("compose.yml")], &super::LoadOptions::default())
+                .unwrap();
+
+        assert!(project.compose.services.contains_key("web"));
+        assert!(project.compose.services.contains_key("db"));
+
+        let location = project.location("services.db.privileged");
+        assert_eq!(location.path, dir.join("db/compose.yml"));
+        assert_eq!(location.line(), Some(3));
+    }
+
+    #[test]
+    fn load_include_cycle() {
+        let temp = write(&[
+            ("a.yml", "include: [b.yml]\nservices: {}\n"),
+            ("b.yml", "include: [a.yml]\nservices: {}\n"),
+        ]);
+        let dir = temp.path();
+
+        let result = super::ComposeFile::load(&[dir.join("a.yml")], &super::LoadOptions::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn load_anchors() {
+        let temp = write(&[(
+            "compose.yml",
+            "x-common: &common\n  privileged: true\n  cap_add: [NET_ADMIN]\nservices:\n  web:\n    <<: *common\n    image: nginx\n",
+        )]);
+        let dir = temp.path();
+
+        let project =
+            super::ComposeFile::load(&[dir.join("compose.yml")], &super::LoadOptions::default())
+                .unwrap();
+        let web = project.compose.services.get("web").unwrap();
+
+        assert_eq!(web.privileged, Some(true));
+        assert_eq!(web.cap_add, Some(vec![String::from("NET_ADMIN")]));
+        assert!(!web.extensions.contains_key("<<"));
+        assert!(project.compose.extensions.contains_key("x-common"));
+
+        let location = project.location("services.web.privileged");
+        assert_eq!(location.line(), Some(1));
+        assert_eq!(location.anchor.unwrap().key, "x-common");
+    }
+
+    #[test]
+    fn load_profiles() {
+        let temp = write(&[(
+            "compose.yml",
+            "services:\n  web:\n    image: nginx\n  debug.tools:\n    image: busybox\n    privileged: true\n    profiles: [debug]\n    depends_on: [db]\n  db:\n    image: postgres\n    profiles: [database]\n  metrics:\n    image: prom/prometheus\n    profiles: [monitoring]\n",
+        )]);
+        let dir = temp.path();
+        let files = [dir.join("compose.yml")];
+        let services = |options: &super::LoadOptions| {
+            let project = super::ComposeFile::load(&files, options).unwrap();
+            let mut names: Vec<String> = project.compose.services.into_keys().collect();
+            names.sort();
+            names
+        };
+
+        // Every service is scanned by default
+        let project = super::ComposeFile::load(&files, &super::LoadOptions::default()).unwrap();
+        assert_eq!(project.compose.services.len(), 4);
+        let location = project.location("services.debug.tools.privileged");
+        assert_eq!(location.profiles, vec![String::from("debug")]);
+        assert!(project.location("services.web.image").profiles.is_empty());
+
+        // Selected profiles enable the services they depend on
+        let options = super::LoadOptions {
+            profiles: vec![String::from("debug")],
+            ..Default::default()
+        };
+        assert_eq!(services(&options), vec!["db", "debug.tools", "web"]);
+
+        let options = super::LoadOptions {
+            profiles: vec![String::from("monitoring")],
+            ..Default::default()
+        };
+        assert_eq!(services(&options), vec!["metrics", "web"]);
+
+        let options = super::LoadOptions {
+            profiles: vec![String::from("monitoring")],
+            all_profiles: true,
+            ..Default::default()
+        };
+        assert_eq!(services(&options).len(), 4);
+    }
+
+    #[test]
+    fn load_violations() {
+        let temp = write(&[
+            (
+                "compose.yml",
+                "services:\n  web:\n    image: nginx\n  debug:\n    image: busybox\n    privilaged: true\n    profiles: [debug]\n",
+            ),
+            (
+                "compose.override.yml",
+                "services:\n  web:\n    read-only: true\n",
+            ),
+        ]);
+        let dir = temp.path();
+        let files = [dir.join("compose.yml"), dir.join("compose.override.yml")];
+
+        let options = super::LoadOptions {
+            profiles: vec![String::from("release")],
+            ..Default::default()
+        };
+        let project = super::ComposeFile::load(&files, &options).unwrap();
+        assert_eq!(project.violations.len(), 1);
+        let violation = &project.violations[0];
+        assert_eq!(violation.key, "services.web.read-only");
+        assert_eq!(violation.suggestion.as_deref(), Some("read_only"));
+
+        let location = project.location(&violation.key);
+        assert_eq!(location.path, dir.join("compose.override.yml"));
+        assert_eq!(location.line(), Some(2));
+
+        let project = super::ComposeFile::load(&files, &super::LoadOptions::default()).unwrap();
+        assert_eq!(project.violations.len(), 2);
+    }
+
+    #[test]
+    fn fix_overrides() {
+        let temp = write(&[
+            (
+                "compose.yml",
+                "services:\n  web:\n    image: nginx\n    ports:\n      - \"8080:80\"\n",
+            ),
+            (
+                "compose.override.yml",
+                "services:\n  web:\n    image: nginx\n    ports:\n      - \"127.0.0.1:443:443\"\n",
+            ),
+        ]);
+        let dir = temp.path();
+        let files = [dir.join("compose.yml"), dir.join("compose.override.yml")];
+        let mut project = super::ComposeFile::load(&files, &super::LoadOptions::default()).unwrap();
+        let change = || super::Change::Set(String::from("127.0.0.1:8080:80"));
+
+        let fix = project.fix("services.web.ports[0]", "", change()).unwrap();
+        assert_eq!(fix.edits[0].path, dir.join("compose.yml"));
+
+        // A location in the wrong file does not edit the value there
+        let overrides = std::fs::read_to_string(&files[1]).unwrap();
+        let region = super::yaml::mappings(&overrides).unwrap().regions["services.web.ports[0]"];
+        let key = String::from("services.web.ports[0]");
+        project.mappings.insert(key.clone(), region);
+        project.sources.insert(key, files[1].clone());
+        assert!(project.fix("services.web.ports[0]", "", change()).is_none());
+    }
+
+    #[test]
+    fn find_diagnostics() {
+        let temp = write(&[
+            (
+                "a/compose.yml",
+                "services:\n  web:\n    image: nginx\n   bad: [\n",
+            ),
+            (
+                "b/compose.yml",
+                "services:\n  web:\n    image: nginx\n    ports:\n      - \"abc:80\"\n",
+            ),
+            ("c/compose.yml", "services:\n  web:\n    image: nginx\n"),
+            (
+                "d/stack.yml",
+                "services:\n  web:\n    image: nginx\n   bad: [\n",
+            ),
+        ]);
+        let dir = temp.path();
+
+        let mut diagnostics = Vec::new();
+        let projects = super::find(
+            dir,
+            &super::LoadOptions::default(),
+            &super::DiscoveryOptions::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(projects.len(), 1);
+        assert_eq!(diagnostics.len(), 3);
+
+        assert_eq!(diagnostics[0].path.path, dir.join("a/compose.yml"));
+        assert_eq!(diagnostics[0].path.line(), Some(3));
+
+        assert_eq!(diagnostics[1].path.path, dir.join("b/compose.yml"));
+        assert_eq!(diagnostics[1].path.line(), Some(4));
+        assert!(diagnostics[1]
+            .message
+            .starts_with("services.web.ports[0]: Invalid port"));
+
+        // Files with other names are not skipped when they fail to parse
+        assert_eq!(diagnostics[2].path.path, dir.join("d/stack.yml"));
+    }
 }
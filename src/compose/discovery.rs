@@ -0,0 +1,277 @@
+//! Discovery of compose files in a directory tree
+use anyhow::Result;
+use ignore::{gitignore::GitignoreBuilder, overrides::OverrideBuilder, WalkBuilder};
+use log::debug;
+use serde_yaml::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::compose::project;
+
+/// Directories that never contain compose files worth scanning
+pub const EXCLUDED_DIRECTORIES: &[&str] = &[".git", "node_modules", "vendor", ".venv"];
+
+/// Ignore file with `.gitignore` syntax for files Quibble should skip
+pub const IGNORE_FILE: &str = ".quibbleignore";
+
+#[derive(Debug, Default, Clone)]
+/// Options used when discovering compose files
+pub struct DiscoveryOptions {
+    /// Globs (relative to the scanned path) of files and directories to skip
+    pub exclude: Vec<String>,
+    /// Maximum directory depth to descend into
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links, symlinked files and directories are skipped
+    /// otherwise
+    pub follow_symlinks: bool,
+}
+
+/// Find the compose files under a directory.
+///
+/// `.gitignore`, `.quibbleignore` and the `exclude` globs are honoured, and
+/// `EXCLUDED_DIRECTORIES` are never walked.
+pub fn discover(path: &Path, options: &DiscoveryOptions) -> Result<Vec<PathBuf>> {
+    let mut overrides = OverrideBuilder::new(path);
+    for exclude in &options.exclude {
+        overrides.add(&format!("!{exclude}"))?;
+    }
+
+    let walker = WalkBuilder::new(path)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .max_depth(options.max_depth)
+        .follow_links(options.follow_symlinks)
+        .overrides(overrides.build()?)
+        .filter_entry(|entry| {
+            let excluded = entry.depth() > 0
+                && entry.file_type().map(|t| t.is_dir()).unwrap_or(false)
+                && EXCLUDED_DIRECTORIES
+                    .iter()
+                    .any(|d| entry.file_name() == std::ffi::OsStr::new(d));
+            !excluded
+        })
+        .build();
+
+    let mut paths = Vec::new();
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                debug!("Skipping path: {err}");
+                continue;
+            }
+        };
+
+        if entry.path_is_symlink() && !options.follow_symlinks {
+            debug!("Skipping symlink: {}", entry.path().display());
+            continue;
+        }
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+
+        if is_compose(entry.path()) {
+            paths.push(entry.into_path());
+        } else {
+            debug!("Not a compose file: {}", entry.path().display());
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// File is ignored by the `.gitignore` files of its git repository, `None`
+/// when it is not in a git repository
+pub fn git_ignored(path: &Path) -> Option<bool> {
+    let path = fs::canonicalize(path).ok()?;
+    let root = path.ancestors().find(|dir| dir.join(".git").exists())?;
+
+    // The closest `.gitignore` with a matching pattern wins
+    for directory in path.ancestors().skip(1) {
+        let mut builder = GitignoreBuilder::new(directory);
+        builder.add(directory.join(".gitignore"));
+        if directory == root {
+            builder.add(root.join(".git").join("info").join("exclude"));
+        }
+        if let Ok(gitignore) = builder.build() {
+            let matched = gitignore.matched_path_or_any_parents(&path, false);
+            if matched.is_ignore() {
+                return Some(true);
+            }
+            if matched.is_whitelist() {
+                return Some(false);
+            }
+        }
+        if directory == root {
+            break;
+        }
+    }
+    Some(false)
+}
+
+/// File is a compose file, either by name (`compose.yml`,
+/// `docker-compose.prod.yml`) or a YAML file with compose content
+pub fn is_compose(path: &Path) -> bool {
+    if project::is_compose_name(path) {
+        return true;
+    }
+
+    let yaml = match path.extension() {
+        Some(ext) => ext == "yml" || ext == "yaml",
+        None => false,
+    };
+
+    yaml && fs::read_to_string(path)
+        .map(|data| is_compose_content(&data))
+        .unwrap_or(false)
+}
+
+/// YAML content is a compose file: it defines a top-level `services` (a
+/// mapping of service mappings) or `include`.
+///
+/// This keeps out YAML that happens to have a `services` key, such as
+/// GitHub workflows (nested under `jobs`) or GitLab CI (`services` is a
+/// list). Unknown top-level keys, such as a misspelled `networks`, are left
+/// to schema validation to report.
+/// Invalid YAML with a top-level `services` key is a broken compose file, so
+/// it is loaded and reported rather than skipped.
+pub fn is_compose_content(data: &str) -> bool {
+    let value: Value = match serde_yaml::from_str(data) {
+        Ok(value) => value,
+        Err(_) => return data.lines().any(|line| line.starts_with("services:")),
+    };
+    let mapping = match value.as_mapping() {
+        Some(mapping) => mapping,
+        None => return false,
+    };
+
+    match mapping.get("services") {
+        Some(Value::Mapping(services)) => services
+            .values()
+            .all(|service| service.is_mapping() || service.is_null()),
+        Some(Value::Null) | None => mapping.contains_key("include"),
+        Some(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_content() {
+        assert!(is_compose_content(
+            "version: '3'\nservices:\n  web:\n    image: nginx\nx-common: {}\n"
+        ));
+        assert!(is_compose_content("include:\n  - db/compose.yml\n"));
+        // Typos are reported by schema validation
+        assert!(is_compose_content(
+            "services:\n  web:\n    image: nginx\nnetwroks: {}\n"
+        ));
+        assert!(!is_compose_content("sevices:\n  web:\n    image: nginx\n"));
+
+        // GitHub workflow
+        assert!(!is_compose_content(
+            "on: push\njobs:\n  test:\n    services:\n      db:\n        image: postgres\n"
+        ));
+        // GitLab CI
+        assert!(!is_compose_content("services:\n  - docker:dind\n"));
+        assert!(!is_compose_content("services: [web]\n"));
+        assert!(!is_compose_content("key: [unclosed\n"));
+        // Broken compose file
+        assert!(is_compose_content(
+            "services:\n  web:\n    image: nginx\n   bad: [\n"
+        ));
+    }
+
+    #[test]
+    fn discover_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let files = [
+            ("docker-compose.yml", "services: {}\n"),
+            ("app/compose.prod.yaml", "services: {}\n"),
+            ("app/stack.yml", "services:\n  web:\n    image: nginx\n"),
+            (".github/workflows/ci.yml", "on: push\njobs: {}\n"),
+            ("node_modules/pkg/compose.yml", "services: {}\n"),
+            ("ignored/compose.yml", "services: {}\n"),
+            ("excluded/compose.yml", "services: {}\n"),
+            ("deep/a/b/compose.yml", "services: {}\n"),
+            (".gitignore", "ignored/\n"),
+            (".quibbleignore", "deep/\n"),
+        ];
+        for (name, data) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+
+        let options = DiscoveryOptions {
+            exclude: vec![String::from("excluded/**")],
+            ..Default::default()
+        };
+        let paths = discover(dir, &options).unwrap();
+
+        assert_eq!(
+            paths,
+            vec![
+                dir.join("app/compose.prod.yaml"),
+                dir.join("app/stack.yml"),
+                dir.join("docker-compose.yml"),
+            ]
+        );
+
+        let options = DiscoveryOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let paths = discover(dir, &options).unwrap();
+        assert_eq!(paths, vec![dir.join("docker-compose.yml")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn discover_symlinks() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("real")).unwrap();
+        fs::write(dir.join("real/compose.yml"), "services: {}\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("linked")).unwrap();
+
+        let paths = discover(dir, &DiscoveryOptions::default()).unwrap();
+        assert_eq!(paths, vec![dir.join("real/compose.yml")]);
+
+        let options = DiscoveryOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let paths = discover(dir, &options).unwrap();
+        assert_eq!(
+            paths,
+            vec![dir.join("linked/compose.yml"), dir.join("real/compose.yml")]
+        );
+    }
+
+    #[test]
+    fn git_ignored_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("app")).unwrap();
+        fs::write(dir.join(".gitignore"), "*.env\n").unwrap();
+        fs::write(dir.join("app/.gitignore"), "!public.env\n").unwrap();
+        for name in ["app/secret.env", "app/public.env", "app/config.txt"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        assert_eq!(git_ignored(&dir.join("app/secret.env")), Some(true));
+        assert_eq!(git_ignored(&dir.join("app/public.env")), Some(false));
+        assert_eq!(git_ignored(&dir.join("app/config.txt")), Some(false));
+        assert_eq!(git_ignored(&dir.join("app/missing.env")), None);
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::Result;
+use serde_yaml::Value;
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+use crate::compose::{
+    interpolation::{Interpolator, UnresolvedVariable},
+    merge, yaml, LoadError,
+};
+use crate::security::{Anchor, Region};
+
+/// Single compose file (or a partially loaded project) that has been
+/// interpolated but not yet deserialized into a `ComposeSpec`
+pub struct Document {
+    /// Path to the file
+    pub path: PathBuf,
+    /// Raw compose document
+    pub raw: Value,
+    /// Source regions of the compose keys
+    pub mappings: HashMap<String, Region>,
+    /// Anchors that keys were pulled in from with an alias or merge key
+    pub anchors: HashMap<String, Anchor>,
+    /// File that set a key, when it is not `path`
+    pub sources: HashMap<String, PathBuf>,
+    /// Raw values of keys that were changed by interpolation
+    pub interpolated: HashMap<String, String>,
+    /// Variables that could not be resolved during interpolation
+    pub unresolved: Vec<UnresolvedVariable>,
+}
+
+impl Document {
+    /// Read and interpolate a compose file
+    pub fn read(path: &Path, interpolator: &Interpolator) -> Result<Self> {
+        let data =
+            fs::read_to_string(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+
+        let mappings = yaml::mappings(&data)
+            .map_err(|err| LoadError::new(path, Some(yaml::point(err.marker())), err.info()))?;
+
+        let mut raw: Value = serde_yaml::from_str(data.as_str()).map_err(|err| {
+            let region = err.location().map(|l| Region {
+                start_line: l.line() as i32 - 1,
+                start_column: l.column() as i32 - 1,
+                end_line: l.line() as i32 - 1,
+                end_column: l.column() as i32 - 1,
+            });
+            LoadError::new(path, region, err.to_string())
+        })?;
+        // Merge keys (`<<: *common`) are not applied by serde_yaml
+        raw.apply_merge()
+            .map_err(|err| LoadError::new(path, None, err.to_string()))?;
+        let mut unresolved = Vec::new();
+        let interpolated = interpolator.interpolate_value(&mut raw, &mut unresolved);
+
+        Ok(Document {
+            path: path.to_path_buf(),
+            raw,
+            mappings: mappings.regions,
+            anchors: mappings.anchors,
+            sources: HashMap::new(),
+            interpolated,
+            unresolved,
+        })
+    }
+
+    /// File that set a compose key
+    pub fn source(&self, key: &str) -> &Path {
+        self.sources.get(key).unwrap_or(&self.path)
+    }
+
+    /// Merge another document on top of this one
+    pub fn merge(&mut self, overrides: Document) {
+        self.merge_with(overrides, true);
+    }
+
+    /// Merge another document on top of this one. With `keep_parents`, keys
+    /// set by both (`services.web`) stay where they were first defined.
+    pub fn merge_with(&mut self, mut overrides: Document, keep_parents: bool) {
+        let merged = merge::merge(&mut self.raw, overrides.raw.clone());
+
+        // Replaced values lose their locations, and override sequence items
+        // move to their index in the merged sequence
+        for key in merged.replaced() {
+            self.remove(&key);
+        }
+        overrides.rekey(|key| merged.key(key));
+
+        for key in overrides.mappings.keys() {
+            let prefix = format!("{key}.");
+            let parent = overrides.mappings.keys().any(|k| k.starts_with(&prefix));
+            if keep_parents && parent && self.mappings.contains_key(key) {
+                continue;
+            }
+            self.set_location(key.clone(), &overrides, key);
+        }
+        self.interpolated.extend(overrides.interpolated);
+        self.unresolved.extend(overrides.unresolved);
+    }
+
+    /// Locations, interpolated values and unresolved variables of `prefix`
+    /// keys as a new document, with the keys moved to `target`
+    pub fn extract(&self, prefix: &str, target: &str) -> Document {
+        let mut document = Document {
+            path: self.path.clone(),
+            raw: Value::Null,
+            mappings: HashMap::new(),
+            anchors: HashMap::new(),
+            sources: HashMap::new(),
+            interpolated: HashMap::new(),
+            unresolved: Vec::new(),
+        };
+        document.import(self, prefix, target);
+        document
+    }
+
+    /// Remove the location of `key` and its child keys
+    pub fn remove_locations(&mut self, key: &str) {
+        let child = |k: &String| is_child(k, key);
+        self.mappings.retain(|k, _| !child(k));
+        self.anchors.retain(|k, _| !child(k));
+        self.sources.retain(|k, _| !child(k));
+    }
+
+    /// Remove everything known about `key` and its child keys
+    pub fn remove(&mut self, key: &str) {
+        self.remove_locations(key);
+        self.interpolated.retain(|k, _| !is_child(k, key));
+        self.unresolved.retain(|v| !is_child(&v.key, key));
+    }
+
+    /// Move every key, dropping keys that `key` returns `None` for
+    fn rekey(&mut self, key: impl Fn(&str) -> Option<String>) {
+        fn moved<V>(
+            map: HashMap<String, V>,
+            key: &impl Fn(&str) -> Option<String>,
+        ) -> HashMap<String, V> {
+            map.into_iter()
+                .filter_map(|(k, v)| key(&k).map(|k| (k, v)))
+                .collect()
+        }
+        self.mappings = moved(std::mem::take(&mut self.mappings), &key);
+        self.anchors = moved(std::mem::take(&mut self.anchors), &key);
+        self.sources = moved(std::mem::take(&mut self.sources), &key);
+        self.interpolated = moved(std::mem::take(&mut self.interpolated), &key);
+        self.unresolved = std::mem::take(&mut self.unresolved)
+            .into_iter()
+            .filter_map(|mut variable| {
+                variable.key = key(&variable.key)?;
+                Some(variable)
+            })
+            .collect();
+    }
+
+    /// Copy the locations of `prefix` keys in `other` to `target` keys in
+    /// this document, without replacing locations that already exist
+    pub fn import_locations(&mut self, other: &Document, prefix: &str, target: &str) {
+        for key in other.mappings.keys() {
+            let key_target = match retarget(key, prefix, target) {
+                Some(key_target) => key_target,
+                None => continue,
+            };
+            if self.mappings.contains_key(&key_target) {
+                continue;
+            }
+            self.set_location(key_target, other, key);
+        }
+    }
+
+    /// Copy the locations, interpolated values and unresolved variables of
+    /// `prefix` keys in `other` to `target` keys in this document
+    pub fn import(&mut self, other: &Document, prefix: &str, target: &str) {
+        self.import_locations(other, prefix, target);
+        for (key, value) in &other.interpolated {
+            if let Some(key) = retarget(key, prefix, target) {
+                self.interpolated
+                    .entry(key)
+                    .or_insert_with(|| value.clone());
+            }
+        }
+        for variable in &other.unresolved {
+            if let Some(key) = retarget(&variable.key, prefix, target) {
+                self.unresolved.push(UnresolvedVariable {
+                    key,
+                    ..variable.clone()
+                });
+            }
+        }
+    }
+
+    /// Set the location of `key` to the location of `other_key` in `other`
+    fn set_location(&mut self, key: String, other: &Document, other_key: &str) {
+        self.sources
+            .insert(key.clone(), other.source(other_key).to_path_buf());
+        match other.anchors.get(other_key) {
+            Some(anchor) => self.anchors.insert(key.clone(), anchor.clone()),
+            None => self.anchors.remove(&key),
+        };
+        if let Some(region) = other.mappings.get(other_key) {
+            self.mappings.insert(key, *region);
+        }
+    }
+}
+
+/// `key` is `parent` or one of its child keys
+fn is_child(key: &str, parent: &str) -> bool {
+    match key.strip_prefix(parent) {
+        Some(suffix) => parent.is_empty() || suffix.is_empty() || suffix.starts_with(['.', '[']),
+        None => false,
+    }
+}
+
+/// `key` moved from `prefix` to `target`, `None` when it is not a `prefix` key
+fn retarget(key: &str, prefix: &str, target: &str) -> Option<String> {
+    match is_child(key, prefix) {
+        true => Some(format!("{target}{}", &key[prefix.len()..])),
+        false => None,
+    }
+}
+
+/// Canonical path used to detect `include` and `extends` cycles
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,140 @@
+//! `.env` file parsing with dotenv semantics
+use anyhow::Result;
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Quoting style of an env file value
+pub enum Quoting {
+    Unquoted,
+    /// `'value'`, taken literally without interpolation
+    Single,
+    /// `"value"`, with escapes and interpolation
+    Double,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Single `KEY=VALUE` entry of an env file
+pub struct EnvEntry {
+    pub key: String,
+    pub value: String,
+    pub quoting: Quoting,
+    /// Zero based line number of the entry
+    pub line: i32,
+}
+
+/// Read and parse an env file
+pub fn read(path: &Path) -> Result<Vec<EnvEntry>> {
+    let data = fs::read_to_string(path)?;
+    Ok(parse(&data))
+}
+
+/// Parse the contents of an env file.
+///
+/// Supports comments, blank lines, an optional `export` prefix, single and
+/// double quoted values (including multi-line double quoted values) and
+/// inline comments after unquoted values.
+pub fn parse(data: &str) -> Vec<EnvEntry> {
+    let mut entries: Vec<EnvEntry> = Vec::new();
+    let mut lines = data.lines().enumerate();
+
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim_start()),
+            None => (line, ""),
+        };
+        if key.is_empty() {
+            continue;
+        }
+
+        let (value, quoting) = if let Some(rest) = value.strip_prefix('"') {
+            // Double quoted values can span lines and contain escapes
+            let mut value = rest.to_string();
+            while !closes(&value, '"') {
+                match lines.next() {
+                    Some((_, next)) => {
+                        value.push('\n');
+                        value.push_str(next);
+                    }
+                    None => break,
+                }
+            }
+            let end = closing(&value, '"').unwrap_or(value.len());
+            (unescape(&value[..end]), Quoting::Double)
+        } else if let Some(rest) = value.strip_prefix('\'') {
+            let end = rest.find('\'').unwrap_or(rest.len());
+            (rest[..end].to_string(), Quoting::Single)
+        } else {
+            let value = match value.find(" #") {
+                Some(index) => value[..index].trim_end(),
+                None => value.trim_end(),
+            };
+            (value.to_string(), Quoting::Unquoted)
+        };
+
+        entries.push(EnvEntry {
+            key: key.to_string(),
+            value,
+            quoting,
+            line: number as i32,
+        });
+    }
+
+    entries
+}
+
+/// Index of the first unescaped `quote`
+fn closing(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(index),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn closes(value: &str, quote: char) -> bool {
+    closing(value, quote).is_some()
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", "\n")
+        .replace("\\t", "\t")
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_entries() {
+        let data = "# comment\nTAG=1.25\nexport DB_USER=admin\n\nQUOTED=\"a # b\"\nSINGLE='${NOT_INTERPOLATED}'\nINLINE=value # comment\nEMPTY=\nMULTI=\"line1\nline2\"\n";
+        let entries = parse(data);
+
+        let get = |key: &str| entries.iter().find(|e| e.key == key).unwrap();
+
+        assert_eq!(entries.len(), 7);
+        assert_eq!(get("TAG").value, "1.25");
+        assert_eq!(get("TAG").line, 1);
+        assert_eq!(get("DB_USER").value, "admin");
+        assert_eq!(get("QUOTED").value, "a # b");
+        assert_eq!(get("SINGLE").value, "${NOT_INTERPOLATED}");
+        assert_eq!(get("SINGLE").quoting, Quoting::Single);
+        assert_eq!(get("QUOTED").quoting, Quoting::Double);
+        assert_eq!(get("TAG").quoting, Quoting::Unquoted);
+        assert_eq!(get("INLINE").value, "value");
+        assert_eq!(get("EMPTY").value, "");
+        assert_eq!(get("MULTI").value, "line1\nline2");
+    }
+}
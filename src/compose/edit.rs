@@ -0,0 +1,423 @@
+//! Formatting preserving edits of compose files, used to fix alerts
+//!
+//! Edits only replace the text of the changed node, so comments, ordering
+//! and the formatting of the rest of the file are kept. Changes that can not
+//! be made in place (flow mappings, block scalars, removing the only entry
+//! of a mapping, ...) are not made.
+use crate::{
+    compose::yaml::{self, Node, NodeKind},
+    security::Region,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Change to a compose key
+pub enum Change {
+    /// Replace the scalar value of the key
+    Set(String),
+    /// Remove the key or sequence item
+    Remove,
+    /// Add an item to the sequence of the key, adding the key when it is
+    /// missing
+    Append(String),
+}
+
+/// Node of a compose key
+struct Entry<'a> {
+    /// Key node of mapping entries
+    name: Option<&'a Node>,
+    value: &'a Node,
+    /// Mapping or sequence the entry belongs to
+    parent: Option<&'a Node>,
+}
+
+impl Entry<'_> {
+    /// Region of the entry, as recorded in the key mappings
+    fn region(&self) -> Region {
+        match (self.name, &self.value.kind) {
+            (Some(name), NodeKind::Scalar(_) | NodeKind::Alias(_)) => {
+                yaml::join(name.region, self.value.region)
+            }
+            (Some(name), _) => name.region,
+            (None, _) => self.value.region,
+        }
+    }
+
+    /// Number of entries of the parent
+    fn siblings(&self) -> usize {
+        match self.parent.map(|p| &p.kind) {
+            Some(NodeKind::Mapping(entries)) => entries.len(),
+            Some(NodeKind::Sequence(items)) => items.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// Lines of the source being edited
+struct Source<'a> {
+    lines: Vec<&'a str>,
+    /// Line ending used by the file
+    newline: &'static str,
+    /// Source ends with a line ending
+    terminated: bool,
+}
+
+impl<'a> Source<'a> {
+    fn new(data: &'a str) -> Self {
+        Source {
+            lines: data.lines().collect(),
+            newline: match data.contains("\r\n") {
+                true => "\r\n",
+                false => "\n",
+            },
+            terminated: data.ends_with('\n'),
+        }
+    }
+
+    /// Text of a line before a column
+    fn before(&self, line: i32, column: i32) -> String {
+        self.line(line).chars().take(column as usize).collect()
+    }
+
+    /// Text of a line after a column
+    fn after(&self, line: i32, column: i32) -> String {
+        self.line(line).chars().skip(column as usize).collect()
+    }
+
+    /// Text of a single line region
+    fn text(&self, region: Region) -> Option<String> {
+        if region.start_line != region.end_line {
+            return None;
+        }
+        Some(
+            self.line(region.start_line)
+                .chars()
+                .skip(region.start_column as usize)
+                .take((region.end_column - region.start_column) as usize)
+                .collect(),
+        )
+    }
+
+    fn line(&self, line: i32) -> &str {
+        self.lines.get(line as usize).copied().unwrap_or_default()
+    }
+
+    /// Line ends after a column, apart from a comment
+    fn ends(&self, line: i32, column: i32) -> bool {
+        let rest = self.after(line, column);
+        let rest = rest.trim_start();
+        rest.is_empty() || rest.starts_with('#')
+    }
+
+    /// Empty region at the start of the line after `line`, or at the end of
+    /// the file
+    fn next_line(&self, line: i32) -> (Region, &'static str) {
+        match line as usize + 1 < self.lines.len() || self.terminated {
+            true => (at(line + 1, 0), ""),
+            false => (
+                at(line, self.line(line).chars().count() as i32),
+                self.newline,
+            ),
+        }
+    }
+}
+
+/// Empty region at a position
+fn at(line: i32, column: i32) -> Region {
+    Region {
+        start_line: line,
+        start_column: column,
+        end_line: line,
+        end_column: column,
+    }
+}
+
+/// Prefix is the indentation and dash of a block sequence item (`  - `)
+fn is_item_prefix(prefix: &str) -> bool {
+    prefix
+        .trim()
+        .strip_prefix('-')
+        .map(|rest| rest.is_empty())
+        .unwrap_or(false)
+}
+
+/// Node of a compose key (`services.web.ports[0]`). Service names can
+/// contain `.` so the longest matching mapping key is used.
+fn find<'a>(root: &'a Node, key: &str) -> Option<Entry<'a>> {
+    let mut entry = Entry {
+        name: None,
+        value: root,
+        parent: None,
+    };
+    let mut rest = key;
+
+    while !rest.is_empty() {
+        let parent = entry.value;
+        entry = match &parent.kind {
+            NodeKind::Mapping(entries) => {
+                let (name, value) = entries
+                    .iter()
+                    .filter(|(name, _)| {
+                        name.as_str()
+                            .and_then(|name| rest.strip_prefix(name))
+                            .map(|r| r.is_empty() || r.starts_with(['.', '[']))
+                            .unwrap_or(false)
+                    })
+                    .max_by_key(|(name, _)| name.as_str().map(str::len))?;
+                rest = &rest[name.as_str()?.len()..];
+                Entry {
+                    name: Some(name),
+                    value,
+                    parent: Some(parent),
+                }
+            }
+            NodeKind::Sequence(items) => {
+                let (index, remaining) = rest.strip_prefix('[')?.split_once(']')?;
+                rest = remaining;
+                Entry {
+                    name: None,
+                    value: items.get(index.parse::<usize>().ok()?)?,
+                    parent: Some(parent),
+                }
+            }
+            _ => return None,
+        };
+        rest = rest.strip_prefix('.').unwrap_or(rest);
+    }
+
+    Some(entry)
+}
+
+/// Scalar in the quoting style of `original`
+fn scalar(value: &str, original: &str) -> Option<String> {
+    let text = match original.chars().next() {
+        Some('"') => serde_json::to_string(value).ok()?,
+        Some('\'') => format!("'{}'", value.replace('\'', "''")),
+        _ => serde_yaml::to_string(value).ok()?.trim_end().to_string(),
+    };
+    match text.contains('\n') {
+        true => None,
+        false => Some(text),
+    }
+}
+
+/// Text edit (region and replacement) making a change to a compose key,
+/// `None` when the change can not be made in place.
+///
+/// `expected` is the region of the key, or of its parent when appending to
+/// a missing key, and `value` the scalar value of the key in the loaded
+/// project. Both guard against editing a different node, such as the same
+/// key of another file of the project.
+pub fn edit(
+    data: &str,
+    key: &str,
+    expected: Region,
+    value: Option<&str>,
+    change: &Change,
+) -> Option<(Region, String)> {
+    let tree = yaml::parse(data).ok()?;
+    let root = tree.root.as_ref()?;
+    let source = Source::new(data);
+
+    match (find(root, key), change) {
+        (Some(entry), _) if entry.region() != expected => None,
+        (Some(entry), _) if value.is_some() && entry.value.as_str() != value => None,
+        (Some(entry), Change::Set(value)) => set(&source, &entry, value),
+        (Some(entry), Change::Remove) => remove(&source, &entry),
+        (Some(entry), Change::Append(item)) => append(&source, &entry, item),
+        (None, Change::Append(item)) => {
+            let (parent, name) = key.rsplit_once('.')?;
+            let parent = find(root, parent).filter(|p| p.region() == expected)?;
+            insert(&source, &parent, name, item)
+        }
+        (None, _) => None,
+    }
+}
+
+/// Replace a single line scalar
+fn set(source: &Source, entry: &Entry, value: &str) -> Option<(Region, String)> {
+    let NodeKind::Scalar(_) = entry.value.kind else {
+        return None;
+    };
+    let original = source.text(entry.value.region)?;
+    if original.starts_with(['|', '>']) {
+        return None;
+    }
+    Some((entry.value.region, scalar(value, &original)?))
+}
+
+/// Remove the lines of a block mapping entry or sequence item
+fn remove(source: &Source, entry: &Entry) -> Option<(Region, String)> {
+    // Removing the only entry would leave an empty (null) value
+    if entry.siblings() < 2 {
+        return None;
+    }
+    let start = entry.name.unwrap_or(entry.value).region;
+    let prefix = source.before(start.start_line, start.start_column);
+    let block = match entry.name {
+        Some(_) => prefix.trim().is_empty(),
+        None => is_item_prefix(&prefix),
+    };
+    let end = entry.value.region;
+    if !block || !source.ends(end.end_line, end.end_column) {
+        return None;
+    }
+
+    let (next, _) = source.next_line(end.end_line);
+    let region = Region {
+        start_line: start.start_line,
+        start_column: 0,
+        end_line: next.end_line,
+        end_column: next.end_column,
+    };
+    Some((region, String::new()))
+}
+
+/// Add an item to the end of a sequence
+fn append(source: &Source, entry: &Entry, item: &str) -> Option<(Region, String)> {
+    let NodeKind::Sequence(items) = &entry.value.kind else {
+        return None;
+    };
+    let region = entry.value.region;
+
+    // Flow sequence (`[a, b]`)
+    if source
+        .after(region.start_line, region.start_column)
+        .starts_with('[')
+    {
+        let item = serde_json::to_string(item).ok()?;
+        return match items.last() {
+            Some(last) => Some((
+                at(last.region.end_line, last.region.end_column),
+                format!(", {item}"),
+            )),
+            None => Some((at(region.end_line, region.end_column - 1), item)),
+        };
+    }
+
+    let first = items.first()?.region;
+    let last = items.last()?.region;
+    let prefix = source.before(first.start_line, first.start_column);
+    if !is_item_prefix(&prefix) || !source.ends(last.end_line, last.end_column) {
+        return None;
+    }
+    let (position, leading) = source.next_line(last.end_line);
+    Some((
+        position,
+        format!("{leading}{prefix}{}{}", scalar(item, "")?, source.newline),
+    ))
+}
+
+/// Add a key with a single item sequence to the end of a block mapping
+fn insert(source: &Source, parent: &Entry, name: &str, item: &str) -> Option<(Region, String)> {
+    let NodeKind::Mapping(entries) = &parent.value.kind else {
+        return None;
+    };
+    let (first, _) = entries.first()?;
+    let (_, last) = entries.last()?;
+    let indent = source.before(first.region.start_line, first.region.start_column);
+    let end = last.region;
+    if !indent.trim().is_empty() || !source.ends(end.end_line, end.end_column) {
+        return None;
+    }
+
+    let (position, leading) = source.next_line(end.end_line);
+    let newline = source.newline;
+    Some((
+        position,
+        format!(
+            "{leading}{indent}{name}:{newline}{indent}  - {}{newline}",
+            scalar(item, "")?
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::Edit;
+
+    /// Apply a change to the key of a YAML document
+    fn change(data: &str, key: &str, change: Change) -> Option<String> {
+        let mappings = yaml::mappings(data).unwrap().regions;
+        let expected = match (mappings.get(key), &change) {
+            (Some(region), _) => *region,
+            (None, Change::Append(_)) => *mappings.get(key.rsplit_once('.')?.0)?,
+            (None, _) => return None,
+        };
+        let (region, text) = edit(data, key, expected, None, &change)?;
+
+        Some(crate::fix::apply(
+            data,
+            &[Edit {
+                path: Default::default(),
+                region,
+                text,
+            }],
+        ))
+    }
+
+    #[test]
+    fn edits() {
+        let data = "services:\n  web.api:\n    image: nginx # web\n    privileged: true\n    ports:\n      - \"8080:80\"\n      - 443:443\n    security_opt: [\"seccomp:unconfined\"]\n  db:\n    image: postgres\n\n    # data\n    volumes:\n      - db:/var/lib/postgresql/data\n";
+
+        assert_eq!(
+            change(data, "services.web.api.privileged", Change::Remove).unwrap(),
+            data.replace("    privileged: true\n", "")
+        );
+        assert_eq!(
+            change(
+                data,
+                "services.web.api.ports[0]",
+                Change::Set("127.0.0.1:8080:80".to_string())
+            )
+            .unwrap(),
+            data.replace("\"8080:80\"", "\"127.0.0.1:8080:80\"")
+        );
+        assert_eq!(
+            change(
+                data,
+                "services.web.api.ports[1]",
+                Change::Set("127.0.0.1:443:443".to_string())
+            )
+            .unwrap(),
+            data.replace("- 443:443", "- 127.0.0.1:443:443")
+        );
+        assert_eq!(
+            change(
+                data,
+                "services.web.api.security_opt",
+                Change::Append("no-new-privileges:true".to_string())
+            )
+            .unwrap(),
+            data.replace(
+                "\"seccomp:unconfined\"]",
+                "\"seccomp:unconfined\", \"no-new-privileges:true\"]"
+            )
+        );
+        assert_eq!(
+            change(
+                data,
+                "services.db.security_opt",
+                Change::Append("no-new-privileges:true".to_string())
+            )
+            .unwrap(),
+            format!("{data}    security_opt:\n      - no-new-privileges:true\n")
+        );
+        assert_eq!(
+            change(
+                data,
+                "services.db.volumes",
+                Change::Append("logs:/var/log".to_string())
+            )
+            .unwrap(),
+            format!("{data}      - logs:/var/log\n")
+        );
+
+        // Removing the only item would leave a null value
+        let data = "services:\n  web:\n    image: nginx\n    cap_add:\n      - SYS_ADMIN\n";
+        assert!(change(data, "services.web.cap_add[0]", Change::Remove).is_none());
+        // Flow mappings are not edited
+        let data = "services:\n  web: {image: nginx, privileged: true}\n";
+        assert!(change(data, "services.web.privileged", Change::Remove).is_none());
+    }
+}
@@ -0,0 +1,133 @@
+//! Resolve services that `extends` another service
+//!
+//! https://github.com/compose-spec/compose-spec/blob/master/05-services.md#extends
+use anyhow::{anyhow, bail, Result};
+use serde_yaml::{Mapping, Value};
+use std::path::PathBuf;
+
+use crate::compose::{
+    document::{canonical, Document},
+    interpolation::Interpolator,
+};
+
+/// Resolve the `extends` chain of every service in the document
+pub fn resolve(document: &mut Document, interpolator: &Interpolator) -> Result<()> {
+    let names: Vec<String> = match document.raw.get("services").and_then(|s| s.as_mapping()) {
+        Some(services) => services
+            .iter()
+            .filter(|(_, service)| service.get("extends").is_some())
+            .filter_map(|(name, _)| name.as_str().map(|n| n.to_string()))
+            .collect(),
+        None => return Ok(()),
+    };
+
+    let mut resolved: Vec<(String, Document)> = Vec::new();
+    for name in names {
+        let mut stack = vec![(canonical(&document.path), name.clone())];
+        resolved.push((
+            name.clone(),
+            resolve_service(document, &name, interpolator, &mut stack)?,
+        ));
+    }
+
+    for (name, service) in resolved {
+        let value = service.raw["services"][name.as_str()].clone();
+        if let Some(services) = document
+            .raw
+            .get_mut("services")
+            .and_then(|s| s.as_mapping_mut())
+        {
+            services.insert(Value::from(name.as_str()), value);
+        }
+
+        let prefix = format!("services.{name}");
+        document.remove(&prefix);
+        document.import(&service, &prefix, &prefix);
+    }
+
+    Ok(())
+}
+
+/// Fully resolved service as a document containing only that service
+fn resolve_service(
+    document: &Document,
+    name: &str,
+    interpolator: &Interpolator,
+    stack: &mut Vec<(PathBuf, String)>,
+) -> Result<Document> {
+    let mut service = document
+        .raw
+        .get("services")
+        .and_then(|s| s.get(name))
+        .cloned()
+        .ok_or_else(|| anyhow!("Service `{name}` not found in {}", document.path.display()))?;
+
+    let prefix = format!("services.{name}");
+    let mut result = document.extract(&prefix, &prefix);
+
+    let extends = match service.as_mapping_mut().and_then(|s| s.remove("extends")) {
+        Some(extends) => extends,
+        None => {
+            result.raw = wrap(name, service);
+            return Ok(result);
+        }
+    };
+
+    let (file, base) = match &extends {
+        Value::String(base) => (None, base.to_string()),
+        Value::Mapping(extends) => (
+            extends.get("file").and_then(|f| f.as_str()),
+            extends
+                .get("service")
+                .and_then(|s| s.as_str())
+                .ok_or_else(|| anyhow!("`extends` of `{name}` is missing a service"))?
+                .to_string(),
+        ),
+        _ => bail!("Invalid `extends` for service `{name}`"),
+    };
+
+    let base_path = match file {
+        Some(file) => document
+            .path
+            .parent()
+            .map(|p| p.join(file))
+            .unwrap_or_else(|| PathBuf::from(file)),
+        None => document.path.clone(),
+    };
+
+    let key = (canonical(&base_path), base.clone());
+    if stack.contains(&key) {
+        bail!(
+            "Circular `extends` reference: service `{base}` in {}",
+            base_path.display()
+        );
+    }
+    stack.push(key);
+
+    let base_service = match file {
+        Some(_) => {
+            let base_document = Document::read(&base_path, interpolator)?;
+            resolve_service(&base_document, &base, interpolator, stack)?
+        }
+        None => resolve_service(document, &base, interpolator, stack)?,
+    };
+    stack.pop();
+
+    // Base service renamed to the extending service, with the extending
+    // service merged on top of it
+    let mut extended = base_service.extract(&format!("services.{base}"), &prefix);
+    extended.path = document.path.clone();
+    extended.raw = wrap(name, base_service.raw["services"][base.as_str()].clone());
+    result.raw = wrap(name, service);
+    extended.merge_with(result, false);
+
+    Ok(extended)
+}
+
+fn wrap(name: &str, service: Value) -> Value {
+    let mut services = Mapping::new();
+    services.insert(Value::from(name), service);
+    let mut document = Mapping::new();
+    document.insert(Value::from("services"), Value::Mapping(services));
+    Value::Mapping(document)
+}
@@ -0,0 +1,122 @@
+//! Resolve the top-level `include` of other compose projects
+//!
+//! https://github.com/compose-spec/compose-spec/blob/master/14-include.md
+use anyhow::{bail, Result};
+use log::debug;
+use serde_yaml::Value;
+use std::path::PathBuf;
+
+use crate::compose::{document::Document, merge, ComposeFile, LoadOptions};
+
+/// Top-level resources that included projects add to the including project
+const SECTIONS: &[&str] = &[
+    "services", "networks", "volumes", "secrets", "configs", "models",
+];
+
+/// Load every included project and add its resources to the document.
+///
+/// `stack` holds the files currently being loaded to detect cycles.
+pub fn resolve(
+    document: &mut Document,
+    options: &LoadOptions,
+    stack: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let includes = match document
+        .raw
+        .as_mapping_mut()
+        .and_then(|m| m.remove("include"))
+    {
+        Some(Value::Sequence(includes)) => includes,
+        Some(_) => bail!("`include` must be a list in {}", document.path.display()),
+        None => return Ok(()),
+    };
+    let directory = document
+        .path
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+
+    for include in includes {
+        let (paths, env_files) = match &include {
+            Value::String(path) => (vec![path.to_string()], vec![]),
+            Value::Mapping(include) => {
+                if include.contains_key("project_directory") {
+                    debug!("`project_directory` of included projects is not supported");
+                }
+                (
+                    strings(include.get("path")),
+                    strings(include.get("env_file")),
+                )
+            }
+            _ => bail!("Invalid `include` entry in {}", document.path.display()),
+        };
+
+        let files: Vec<PathBuf> = paths.iter().map(|p| directory.join(p)).collect();
+        let options = LoadOptions {
+            env_files: env_files.iter().map(|p| directory.join(p)).collect(),
+            ..options.clone()
+        };
+
+        debug!("Including compose file(s): {:?}", files);
+        let included = ComposeFile::load_documents(&files, &options, stack)?;
+
+        let mut keys = Vec::new();
+        for section in SECTIONS {
+            let resources = match included.raw.get(section).and_then(|r| r.as_mapping()) {
+                Some(resources) => resources,
+                None => continue,
+            };
+            let root = document
+                .raw
+                .as_mapping_mut()
+                .ok_or_else(|| anyhow::anyhow!("Invalid compose file: {}", directory.display()))?;
+            let target = root
+                .entry(Value::from(*section))
+                .or_insert_with(|| Value::Mapping(Default::default()));
+            if target.is_null() {
+                *target = Value::Mapping(Default::default());
+            }
+            let target = match target.as_mapping_mut() {
+                Some(target) => target,
+                None => bail!(
+                    "`{section}` must be a mapping in {}",
+                    document.path.display()
+                ),
+            };
+
+            for (name, resource) in resources {
+                if target.contains_key(name) {
+                    bail!(
+                        "Included project {} defines conflicting {section} `{}`",
+                        included.path.display(),
+                        name.as_str().unwrap_or_default()
+                    );
+                }
+                target.insert(name.clone(), resource.clone());
+                keys.push(format!("{section}.{}", merge::key_string(name)));
+            }
+        }
+
+        // Only the locations of the added resources are imported, not the
+        // other keys of the included project (its own `include`)
+        for key in keys {
+            document.remove_locations(&key);
+            document.import_locations(&included, &key, &key);
+        }
+        document.interpolated.extend(included.interpolated);
+        document.unresolved.extend(included.unresolved);
+    }
+
+    Ok(())
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(s)) => vec![s.to_string()],
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
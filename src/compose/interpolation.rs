@@ -0,0 +1,412 @@
+//! Compose variable interpolation
+//!
+//! https://github.com/compose-spec/compose-spec/blob/master/12-interpolation.md
+use anyhow::Result;
+use log::debug;
+use serde_yaml::Value;
+use std::{collections::HashMap, path::Path};
+use thiserror::Error;
+
+use crate::compose::{
+    dotenv::{self, Quoting},
+    LoadOptions,
+};
+
+/// Attributes that are booleans once interpolated
+const BOOLEANS: &[&str] = &[
+    "attach",
+    "attachable",
+    "disable",
+    "enable_ipv4",
+    "enable_ipv6",
+    "external",
+    "init",
+    "internal",
+    "no_cache",
+    "oom_kill_disable",
+    "privileged",
+    "pull",
+    "read_only",
+    "required",
+    "stdin_open",
+    "tty",
+    "use_api_socket",
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InterpolationError {
+    #[error("required variable `{variable}` is missing a value: {message}")]
+    Required { variable: String, message: String },
+    #[error("invalid interpolation format for `{0}`")]
+    Syntax(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Variable that could not be resolved while interpolating a compose file
+pub struct UnresolvedVariable {
+    /// Compose key the variable is used in
+    pub key: String,
+    /// Variable name (or the invalid expression)
+    pub variable: String,
+    /// Error message for required variables and syntax errors
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+/// Resolves `${VAR}` style variables
+pub struct Interpolator {
+    pub variables: HashMap<String, String>,
+}
+
+impl Interpolator {
+    pub fn new() -> Self {
+        Interpolator {
+            ..Default::default()
+        }
+    }
+
+    /// Interpolator for a project directory: the project `.env` (or the
+    /// `--env-file` files instead) and optionally the process environment,
+    /// which has precedence
+    pub fn for_project(directory: &Path, options: &LoadOptions) -> Result<Self> {
+        let mut interpolator = Interpolator::new();
+
+        if options.env_files.is_empty() {
+            let default = directory.join(".env");
+            if default.is_file() {
+                interpolator.load_env_file(&default)?;
+            }
+        } else {
+            for env_file in &options.env_files {
+                interpolator.load_env_file(env_file)?;
+            }
+        }
+
+        if options.process_env {
+            interpolator.variables.extend(std::env::vars());
+        }
+
+        Ok(interpolator)
+    }
+
+    /// Load variables from an env file, values can reference variables
+    /// defined before them unless they are single quoted
+    pub fn load_env_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Loading env file: {}", path.display());
+
+        for entry in dotenv::read(path)? {
+            let value = match entry.quoting {
+                Quoting::Single => entry.value,
+                Quoting::Unquoted | Quoting::Double => {
+                    let mut unset = Vec::new();
+                    self.interpolate(&entry.value, &mut unset)
+                        .unwrap_or(entry.value)
+                }
+            };
+            self.variables.insert(entry.key, value);
+        }
+        Ok(())
+    }
+
+    /// Interpolate a string. Variables that are not set (and have no
+    /// default) are replaced with an empty string and added to `unset`.
+    pub fn interpolate(
+        &self,
+        input: &str,
+        unset: &mut Vec<String>,
+    ) -> Result<String, InterpolationError> {
+        let mut output = String::new();
+        let mut chars = input.char_indices().peekable();
+
+        while let Some((index, c)) = chars.next() {
+            if c != '$' {
+                output.push(c);
+                continue;
+            }
+
+            match chars.peek().map(|(_, c)| *c) {
+                Some('$') => {
+                    chars.next();
+                    output.push('$');
+                }
+                Some('{') => {
+                    chars.next();
+                    let start = index + 2;
+                    let mut depth = 1;
+                    let mut end = None;
+
+                    for (i, c) in chars.by_ref() {
+                        match c {
+                            '{' => depth += 1,
+                            '}' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    end = Some(i);
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    let end = end.ok_or_else(|| InterpolationError::Syntax(input.to_string()))?;
+                    output.push_str(&self.evaluate(&input[start..end], unset)?);
+                }
+                Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                    let mut name = String::new();
+                    while let Some((_, c)) = chars.peek() {
+                        if *c == '_' || c.is_ascii_alphanumeric() {
+                            name.push(*c);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    output.push_str(&self.lookup(&name, unset));
+                }
+                _ => output.push('$'),
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Evaluate the expression inside `${...}`
+    fn evaluate(
+        &self,
+        expression: &str,
+        unset: &mut Vec<String>,
+    ) -> Result<String, InterpolationError> {
+        let name_end = expression
+            .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+            .unwrap_or(expression.len());
+        let (name, operation) = expression.split_at(name_end);
+
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(InterpolationError::Syntax(format!("${{{expression}}}")));
+        }
+
+        let value = self.variables.get(name);
+        let empty = value.map(|v| v.is_empty()).unwrap_or(true);
+
+        let (operator, argument) = match operation {
+            "" => return Ok(self.lookup(name, unset)),
+            o if [":-", ":?", ":+"].iter().any(|p| o.starts_with(p)) => o.split_at(2),
+            o if ["-", "?", "+"].iter().any(|p| o.starts_with(p)) => o.split_at(1),
+            _ => return Err(InterpolationError::Syntax(format!("${{{expression}}}"))),
+        };
+
+        match operator {
+            ":-" if empty => self.interpolate(argument, unset),
+            "-" if value.is_none() => self.interpolate(argument, unset),
+            ":-" | "-" => Ok(value.cloned().unwrap_or_default()),
+            ":?" | "?" if (operator == ":?" && empty) || value.is_none() => {
+                Err(InterpolationError::Required {
+                    variable: name.to_string(),
+                    message: self.interpolate(argument, unset)?,
+                })
+            }
+            ":?" | "?" => Ok(value.cloned().unwrap_or_default()),
+            ":+" if !empty => self.interpolate(argument, unset),
+            "+" if value.is_some() => self.interpolate(argument, unset),
+            ":+" | "+" => Ok(String::new()),
+            _ => Err(InterpolationError::Syntax(format!("${{{expression}}}"))),
+        }
+    }
+
+    fn lookup(&self, name: &str, unset: &mut Vec<String>) -> String {
+        match self.variables.get(name) {
+            Some(value) => value.to_string(),
+            None => {
+                unset.push(name.to_string());
+                String::new()
+            }
+        }
+    }
+
+    /// Interpolate every string value of a compose document.
+    ///
+    /// Returns the raw value of every key that was changed, keyed by the
+    /// compose key (`services.web.image`).
+    pub fn interpolate_value(
+        &self,
+        value: &mut Value,
+        unresolved: &mut Vec<UnresolvedVariable>,
+    ) -> HashMap<String, String> {
+        let mut interpolated = HashMap::new();
+        self.walk(value, String::new(), unresolved, &mut interpolated);
+        interpolated
+    }
+
+    fn walk(
+        &self,
+        value: &mut Value,
+        key: String,
+        unresolved: &mut Vec<UnresolvedVariable>,
+        interpolated: &mut HashMap<String, String>,
+    ) {
+        match value {
+            Value::Mapping(mapping) => {
+                for (k, v) in mapping.iter_mut() {
+                    let name = match k {
+                        Value::String(s) => s.to_string(),
+                        _ => continue,
+                    };
+                    let child = match key.is_empty() {
+                        true => name,
+                        false => format!("{key}.{name}"),
+                    };
+                    self.walk(v, child, unresolved, interpolated);
+                }
+            }
+            Value::Sequence(seq) => {
+                for (index, v) in seq.iter_mut().enumerate() {
+                    self.walk(v, format!("{key}[{index}]"), unresolved, interpolated);
+                }
+            }
+            Value::Tagged(tagged) => self.walk(&mut tagged.value, key, unresolved, interpolated),
+            Value::String(raw) if raw.contains('$') => {
+                let mut unset = Vec::new();
+                let result = match self.interpolate(raw, &mut unset) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        let variable = match &err {
+                            InterpolationError::Required { variable, .. } => variable.to_string(),
+                            InterpolationError::Syntax(expression) => expression.to_string(),
+                        };
+                        unresolved.push(UnresolvedVariable {
+                            key: key.clone(),
+                            variable,
+                            message: Some(err.to_string()),
+                        });
+                        String::new()
+                    }
+                };
+
+                unresolved.extend(unset.into_iter().map(|variable| UnresolvedVariable {
+                    key: key.clone(),
+                    variable,
+                    message: None,
+                }));
+
+                if result != *raw {
+                    interpolated.insert(key.clone(), raw.to_string());
+                }
+
+                let field = key.rsplit('.').next().unwrap_or("");
+                *value = match result.as_str() {
+                    "true" | "false" if BOOLEANS.contains(&field) => Value::Bool(result == "true"),
+                    _ => Value::String(result),
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interpolator() -> Interpolator {
+        let mut interpolator = Interpolator::new();
+        interpolator
+            .variables
+            .insert(String::from("TAG"), String::from("1.25"));
+        interpolator
+            .variables
+            .insert(String::from("EMPTY"), String::new());
+        interpolator
+    }
+
+    fn resolve(input: &str) -> Result<String, InterpolationError> {
+        interpolator().interpolate(input, &mut Vec::new())
+    }
+
+    #[test]
+    fn interpolate_syntax() {
+        assert_eq!(resolve("nginx:${TAG}").unwrap(), "nginx:1.25");
+        assert_eq!(resolve("nginx:$TAG").unwrap(), "nginx:1.25");
+        assert_eq!(resolve("$${TAG} $$TAG").unwrap(), "${TAG} $TAG");
+        assert_eq!(resolve("${MISSING:-latest}").unwrap(), "latest");
+        assert_eq!(resolve("${EMPTY:-default}").unwrap(), "default");
+        assert_eq!(resolve("${EMPTY-default}").unwrap(), "");
+        assert_eq!(resolve("${MISSING-default}").unwrap(), "default");
+        assert_eq!(resolve("${MISSING:-${TAG}}").unwrap(), "1.25");
+        assert_eq!(resolve("${TAG:+set}").unwrap(), "set");
+        assert_eq!(resolve("${MISSING+set}").unwrap(), "");
+    }
+
+    #[test]
+    fn interpolate_required() {
+        assert_eq!(resolve("${TAG:?tag required}").unwrap(), "1.25");
+        assert_eq!(resolve("${EMPTY?must exist}").unwrap(), "");
+        assert_eq!(
+            resolve("${EMPTY:?must not be empty}"),
+            Err(InterpolationError::Required {
+                variable: String::from("EMPTY"),
+                message: String::from("must not be empty"),
+            })
+        );
+        assert!(resolve("${MISSING?err}").is_err());
+        assert!(matches!(
+            resolve("${TAG"),
+            Err(InterpolationError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn interpolate_unset() {
+        let mut unset = Vec::new();
+        let result = interpolator()
+            .interpolate("${MISSING}", &mut unset)
+            .unwrap();
+
+        assert_eq!(result, "");
+        assert_eq!(unset, vec![String::from("MISSING")]);
+    }
+
+    #[test]
+    fn env_file_quoting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "HOME_DIR=/home/app\nSINGLE='abc${HOME_DIR}def'\nDOUBLE=\"abc${HOME_DIR}def\"\nPLAIN=abc${HOME_DIR}def\n",
+        )
+        .unwrap();
+
+        let mut interpolator = Interpolator::new();
+        interpolator.load_env_file(&path).unwrap();
+        let get = |key: &str| interpolator.variables.get(key).unwrap().as_str();
+
+        assert_eq!(get("SINGLE"), "abc${HOME_DIR}def");
+        assert_eq!(get("DOUBLE"), "abc/home/appdef");
+        assert_eq!(get("PLAIN"), "abc/home/appdef");
+    }
+
+    #[test]
+    fn interpolate_document() {
+        let mut value: Value = serde_yaml::from_str(
+            "services:\n  web:\n    image: nginx:${TAG}\n    privileged: ${PRIVILEGED:-true}\n    environment:\n      - PASSWORD=${DB_PASSWORD:?password required}\n",
+        )
+        .unwrap();
+
+        let mut unresolved = Vec::new();
+        let interpolated = interpolator().interpolate_value(&mut value, &mut unresolved);
+
+        assert_eq!(value["services"]["web"]["image"], Value::from("nginx:1.25"));
+        assert_eq!(value["services"]["web"]["privileged"], Value::from(true));
+        assert_eq!(
+            interpolated.get("services.web.image"),
+            Some(&String::from("nginx:${TAG}"))
+        );
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(unresolved[0].key, "services.web.environment[0]");
+        assert_eq!(
+            interpolated.get("services.web.environment[0]"),
+            Some(&String::from("PASSWORD=${DB_PASSWORD:?password required}"))
+        );
+        assert_eq!(unresolved[0].variable, "DB_PASSWORD");
+    }
+}
@@ -0,0 +1,379 @@
+//! Compose file merge rules
+//!
+//! https://github.com/compose-spec/compose-spec/blob/master/13-merge.md
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+use crate::compose::ServiceVolume;
+
+/// Sequences that are replaced instead of merged
+const REPLACED: &[&str] = &["command", "entrypoint"];
+
+/// Sequences where duplicate entries are only kept once
+const UNIQUE: &[&str] = &[
+    "cap_add",
+    "cap_drop",
+    "device_cgroup_rules",
+    "dns",
+    "dns_opt",
+    "dns_search",
+    "env_file",
+    "expose",
+    "external_links",
+    "group_add",
+    "label_file",
+    "links",
+    "ports",
+    "profiles",
+    "security_opt",
+    "tmpfs",
+    "volumes_from",
+];
+
+/// Sequences merged by their mount target
+const BY_TARGET: &[&str] = &["volumes", "devices", "secrets", "configs"];
+
+/// Attributes that are either a list of `KEY=VALUE` or a mapping
+const LIST_OR_MAPPING: &[&str] = &[
+    "annotations",
+    "environment",
+    "extra_hosts",
+    "labels",
+    "sysctls",
+    "args",
+    "options",
+    "networks",
+    "depends_on",
+];
+
+#[derive(Debug, Default)]
+/// Where the values of the overrides ended up in the merged document, so the
+/// locations of their keys can follow them
+pub struct Merged {
+    /// Keys whose base value was replaced or removed
+    replaced: Vec<String>,
+    /// Appended sequences by key: the length of the base sequence and the
+    /// merged index of every override item (`None` for dropped duplicates)
+    sequences: HashMap<String, (usize, Vec<Option<usize>>)>,
+}
+
+impl Merged {
+    /// Keys whose base value was replaced, including base sequence items
+    /// replaced by an override item with the same target
+    pub fn replaced(&self) -> Vec<String> {
+        let mut keys = self.replaced.clone();
+        for (key, (length, indices)) in &self.sequences {
+            keys.extend(
+                indices
+                    .iter()
+                    .flatten()
+                    .filter(|index| *index < length)
+                    .map(|index| format!("{key}[{index}]")),
+            );
+        }
+        keys
+    }
+
+    /// Key of an override key in the merged document, `None` when the value
+    /// was dropped
+    pub fn key(&self, key: &str) -> Option<String> {
+        for (sequence, (_, indices)) in &self.sequences {
+            let rest = match key.strip_prefix(sequence.as_str()) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let (index, suffix) = match rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                Some(index) => index,
+                None => continue,
+            };
+            let index = match index.parse::<usize>() {
+                Ok(index) => index,
+                Err(_) => continue,
+            };
+            return indices
+                .get(index)
+                .copied()
+                .flatten()
+                .map(|index| format!("{sequence}[{index}]{suffix}"));
+        }
+        Some(key.to_string())
+    }
+}
+
+/// Merge `overrides` into `base` following the compose-spec merge rules
+pub fn merge(base: &mut Value, overrides: Value) -> Merged {
+    let mut merged = Merged::default();
+    merge_at(base, overrides, &mut Vec::new(), &mut merged);
+    merged
+}
+
+fn merge_at(base: &mut Value, overrides: Value, path: &mut Vec<String>, merged: &mut Merged) {
+    let field = service_field(path);
+
+    // `!override` replaces the value completely
+    let overrides = match overrides {
+        Value::Tagged(tagged) if tagged.tag == "!override" => {
+            *base = strip_tags(tagged.value);
+            merged.replaced.push(path.join("."));
+            return;
+        }
+        Value::Tagged(tagged) => tagged.value,
+        v => v,
+    };
+
+    match (base, overrides) {
+        (Value::Mapping(base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                path.push(key_string(&key));
+                if is_reset(&value) {
+                    base.remove(&key);
+                    merged.replaced.push(path.join("."));
+                    path.pop();
+                    continue;
+                }
+                match base.get_mut(&key) {
+                    Some(existing) => merge_at(existing, value, path, merged),
+                    None => {
+                        base.insert(key, strip_tags(value));
+                    }
+                }
+                path.pop();
+            }
+        }
+        (base, overrides) if LIST_OR_MAPPING.contains(&field) => {
+            let mut mapping = to_mapping(base, field);
+            let overrides = to_mapping(&overrides, field);
+            match mapping.is_mapping() && overrides.is_mapping() {
+                true => merge_at(&mut mapping, overrides, path, merged),
+                false => {
+                    mapping = strip_tags(overrides);
+                    merged.replaced.push(path.join("."));
+                }
+            }
+            *base = mapping;
+        }
+        (Value::Sequence(base), Value::Sequence(overrides))
+            if !REPLACED.contains(&field) && !is_healthcheck_test(path) =>
+        {
+            let length = base.len();
+            let mut indices = Vec::new();
+            if BY_TARGET.contains(&field) {
+                for value in overrides {
+                    let value = strip_tags(value);
+                    let target = mount_target(field, &value);
+                    match base.iter().position(|b| mount_target(field, b) == target) {
+                        Some(index) if target.is_some() => {
+                            base[index] = value;
+                            indices.push(Some(index));
+                        }
+                        _ => {
+                            indices.push(Some(base.len()));
+                            base.push(value);
+                        }
+                    }
+                }
+            } else {
+                let unique = UNIQUE.contains(&field);
+                for value in overrides {
+                    let value = strip_tags(value);
+                    if !unique || !base.contains(&value) {
+                        indices.push(Some(base.len()));
+                        base.push(value);
+                    } else {
+                        indices.push(None);
+                    }
+                }
+            }
+            merged.sequences.insert(path.join("."), (length, indices));
+        }
+        (base, overrides) => {
+            if base.is_mapping() || base.is_sequence() {
+                merged.replaced.push(path.join("."));
+            }
+            *base = strip_tags(overrides);
+        }
+    }
+}
+
+/// Remove `!reset` / `!override` tags so the value can be deserialized
+pub fn strip_tags(value: Value) -> Value {
+    match value {
+        Value::Tagged(tagged) => strip_tags(tagged.value),
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .filter(|(_, v)| !is_reset(v))
+                .map(|(k, v)| (k, strip_tags(v)))
+                .collect(),
+        ),
+        Value::Sequence(seq) => Value::Sequence(seq.into_iter().map(strip_tags).collect()),
+        v => v,
+    }
+}
+
+fn is_reset(value: &Value) -> bool {
+    matches!(value, Value::Tagged(tagged) if tagged.tag == "!reset")
+}
+
+pub fn key_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.to_string(),
+        _ => serde_yaml::to_string(key)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+/// Name of the attribute being merged (the last path segment), only
+/// considered below `services.<name>`
+fn service_field(path: &[String]) -> &str {
+    match path.first().map(|s| s.as_str()) {
+        Some("services") if path.len() >= 3 => path.last().map(|s| s.as_str()).unwrap_or(""),
+        _ => "",
+    }
+}
+
+fn is_healthcheck_test(path: &[String]) -> bool {
+    path.len() == 4 && path[2] == "healthcheck" && path[3] == "test"
+}
+
+/// Target path of a volume, device, secret or config entry
+fn mount_target(field: &str, value: &Value) -> Option<String> {
+    match value {
+        // Volume sources can be Windows paths (`C:\data:/data`)
+        Value::String(s) if field == "volumes" => {
+            ServiceVolume::parse(s).ok().map(|volume| volume.target)
+        }
+        Value::String(s) => {
+            let parts: Vec<&str> = s.split(':').collect();
+            match parts.len() {
+                1 => Some(parts[0].to_string()),
+                _ => Some(parts[1].to_string()),
+            }
+        }
+        Value::Mapping(m) => m
+            .get("target")
+            .or_else(|| m.get("source"))
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string()),
+        _ => None,
+    }
+}
+
+/// Convert the list form of an attribute into its mapping form
+fn to_mapping(value: &Value, field: &str) -> Value {
+    let entries = match value {
+        Value::Sequence(seq) => seq,
+        Value::Tagged(tagged) => return to_mapping(&tagged.value, field),
+        v => return v.clone(),
+    };
+
+    let mut mapping = Mapping::new();
+    for entry in entries {
+        let entry = match entry {
+            Value::String(s) => s.to_string(),
+            Value::Number(n) => n.to_string(),
+            _ => continue,
+        };
+
+        match field {
+            // Service names and networks without any options
+            "networks" => {
+                mapping.insert(Value::from(entry), Value::Null);
+            }
+            "depends_on" => {
+                let mut condition = Mapping::new();
+                condition.insert(Value::from("condition"), Value::from("service_started"));
+                mapping.insert(Value::from(entry), Value::Mapping(condition));
+            }
+            _ => {
+                let separator = match field {
+                    "extra_hosts" if !entry.contains('=') => ':',
+                    _ => '=',
+                };
+                match entry.split_once(separator) {
+                    Some((key, value)) => mapping.insert(Value::from(key), Value::from(value)),
+                    None => mapping.insert(Value::from(entry), Value::Null),
+                };
+            }
+        }
+    }
+    Value::Mapping(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(base: &str, overrides: &str) -> Value {
+        let mut base: Value = serde_yaml::from_str(base).unwrap();
+        let overrides: Value = serde_yaml::from_str(overrides).unwrap();
+        merge(&mut base, overrides);
+        base
+    }
+
+    #[test]
+    fn merge_mappings() {
+        let value = merged(
+            "services:\n  web:\n    image: nginx\n    restart: always\n",
+            "services:\n  web:\n    image: nginx:1.25\n    privileged: true\n",
+        );
+        let web = &value["services"]["web"];
+
+        assert_eq!(web["image"], Value::from("nginx:1.25"));
+        assert_eq!(web["restart"], Value::from("always"));
+        assert_eq!(web["privileged"], Value::from(true));
+    }
+
+    #[test]
+    fn merge_sequences() {
+        let value = merged(
+            "services:\n  web:\n    ports: [\"80:80\"]\n    command: [run, a]\n    volumes: [\"./a:/data\", \"logs:/logs\"]\n",
+            "services:\n  web:\n    ports: [\"80:80\", \"443:443\"]\n    command: [run, b]\n    volumes: [\"./b:/data\"]\n",
+        );
+        let web = &value["services"]["web"];
+
+        assert_eq!(web["ports"].as_sequence().unwrap().len(), 2);
+        assert_eq!(web["command"][1], Value::from("b"));
+        let volumes = web["volumes"].as_sequence().unwrap();
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0], Value::from("./b:/data"));
+
+        let value = merged(
+            "services:\n  web:\n    volumes: [\"C:\\\\a:/data\", \"C:\\\\logs:/logs\"]\n",
+            "services:\n  web:\n    volumes: [\"D:\\\\b:/data\"]\n",
+        );
+        let volumes = value["services"]["web"]["volumes"].as_sequence().unwrap();
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0], Value::from("D:\\b:/data"));
+    }
+
+    #[test]
+    fn merge_list_and_mapping() {
+        let value = merged(
+            "services:\n  web:\n    environment:\n      - A=1\n      - B=2\n",
+            "services:\n  web:\n    environment:\n      B: 3\n      C: 4\n",
+        );
+        let env = &value["services"]["web"]["environment"];
+
+        assert_eq!(env["A"], Value::from("1"));
+        assert_eq!(env["B"], Value::from(3));
+        assert_eq!(env["C"], Value::from(4));
+    }
+
+    #[test]
+    fn merge_reset_and_override() {
+        let value = merged(
+            "services:\n  web:\n    ports: [\"80:80\"]\n    cap_add: [NET_ADMIN]\n",
+            "services:\n  web:\n    ports: !reset []\n    cap_add: !override [CHOWN]\n",
+        );
+        let web = &value["services"]["web"];
+
+        assert!(web.get("ports").is_none());
+        assert_eq!(
+            web["cap_add"],
+            serde_yaml::from_str::<Value>("[CHOWN]").unwrap()
+        );
+    }
+}
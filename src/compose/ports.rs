@@ -0,0 +1,309 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+use crate::compose::StringOrNumber;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Single port or inclusive range of ports
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn single(port: u16) -> Self {
+        PortRange {
+            start: port,
+            end: port,
+        }
+    }
+
+    pub fn is_range(&self) -> bool {
+        self.start != self.end
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let parse = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|_| anyhow!("Invalid port: `{value}`"))
+        };
+
+        let range = match value.split_once('-') {
+            Some((start, end)) => PortRange {
+                start: parse(start)?,
+                end: parse(end)?,
+            },
+            None => PortRange::single(parse(value)?),
+        };
+
+        if range.start > range.end {
+            return Err(anyhow!("Invalid port range: `{value}`"));
+        }
+        Ok(range)
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_range() {
+            true => write!(f, "{}-{}", self.start, self.end),
+            false => write!(f, "{}", self.start),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Where a port is reachable from on the host
+pub enum PortExposure {
+    /// Bound to every host interface (`0.0.0.0` / `::`)
+    AllInterfaces,
+    /// Bound to a specific host address
+    Interface(String),
+    /// Bound to the loopback interface only
+    Loopback,
+    /// Not published to the host
+    NotPublished,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "PortSyntax")]
+/// Service port binding, parsed from either the short
+/// (`[HOST_IP:][PUBLISHED:]TARGET[/PROTOCOL]`) or long syntax
+pub struct PortBinding {
+    /// Human readable name of the port
+    pub name: Option<String>,
+    /// Host address the port is bound to
+    pub host_ip: Option<String>,
+    /// Host port(s), `None` when an ephemeral port is picked by the engine
+    pub published: Option<PortRange>,
+    /// Container port(s)
+    pub target: PortRange,
+    /// Protocol (`tcp` or `udp`)
+    pub protocol: String,
+    /// Application protocol (`http`, `https`, ...)
+    pub app_protocol: Option<String>,
+    /// Swarm publishing mode (`host` or `ingress`)
+    pub mode: Option<String>,
+}
+
+impl PortBinding {
+    /// Parse the short port syntax
+    pub fn parse(port: &str) -> Result<Self> {
+        let (address, protocol) = match port.rsplit_once('/') {
+            Some((address, protocol)) => (address, protocol.to_string()),
+            None => (port, String::from("tcp")),
+        };
+
+        let mut result = PortBinding {
+            protocol,
+            ..Default::default()
+        };
+
+        // IPv6 host addresses in brackets: `[::1]:8080:80`
+        let address = match address.strip_prefix('[') {
+            Some(rest) => {
+                let (ip, rest) = rest
+                    .split_once("]:")
+                    .ok_or_else(|| anyhow!("Invalid port syntax: `{port}`"))?;
+                result.host_ip = Some(ip.to_string());
+                rest
+            }
+            None => address,
+        };
+
+        let mut parts: Vec<&str> = address.rsplitn(3, ':').collect();
+        parts.reverse();
+
+        match parts.as_slice() {
+            [target] => {
+                result.target = PortRange::from_str(target)?;
+            }
+            [published, target] => {
+                if !published.is_empty() {
+                    result.published = Some(PortRange::from_str(published)?);
+                }
+                result.target = PortRange::from_str(target)?;
+            }
+            [ip, published, target] if result.host_ip.is_none() => {
+                result.host_ip = Some(ip.to_string());
+                if !published.is_empty() {
+                    result.published = Some(PortRange::from_str(published)?);
+                }
+                result.target = PortRange::from_str(target)?;
+            }
+            _ => return Err(anyhow!("Invalid port syntax: `{port}`")),
+        }
+
+        Ok(result)
+    }
+
+    /// Where the port is reachable from on the host
+    pub fn exposure(&self) -> PortExposure {
+        match self.host_ip.as_deref() {
+            None | Some("") | Some("0.0.0.0") | Some("::") => PortExposure::AllInterfaces,
+            Some(ip) if ip.starts_with("127.") || ip == "::1" || ip == "localhost" => {
+                PortExposure::Loopback
+            }
+            Some(ip) => PortExposure::Interface(ip.to_string()),
+        }
+    }
+}
+
+impl Display for PortBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ip) = &self.host_ip {
+            match ip.contains(':') {
+                true => write!(f, "[{ip}]:")?,
+                false => write!(f, "{ip}:")?,
+            }
+        }
+        if let Some(published) = &self.published {
+            write!(f, "{published}:")?;
+        } else if self.host_ip.is_some() {
+            write!(f, ":")?;
+        }
+        write!(f, "{}/{}", self.target, self.protocol)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PortSyntax {
+    Short(StringOrNumber),
+    Long(LongPort),
+}
+
+#[derive(Debug, Deserialize)]
+struct LongPort {
+    name: Option<String>,
+    target: StringOrNumber,
+    published: Option<StringOrNumber>,
+    host_ip: Option<String>,
+    protocol: Option<String>,
+    app_protocol: Option<String>,
+    mode: Option<String>,
+}
+
+/// Port values can be given as either a string or a number
+fn port_string(value: &StringOrNumber) -> Result<String> {
+    match value {
+        StringOrNumber::Num(n) => Ok(n.to_string()),
+        StringOrNumber::Int(n) => Ok(n.to_string()),
+        StringOrNumber::Str(s) => Ok(s.to_string()),
+        _ => Err(anyhow!("Invalid port value: {value:?}")),
+    }
+}
+
+impl TryFrom<PortSyntax> for PortBinding {
+    type Error = anyhow::Error;
+
+    fn try_from(value: PortSyntax) -> Result<Self> {
+        let long = match value {
+            PortSyntax::Short(s) => return PortBinding::parse(&port_string(&s)?),
+            PortSyntax::Long(l) => l,
+        };
+
+        let published = match &long.published {
+            Some(p) => Some(PortRange::from_str(&port_string(p)?)?),
+            None => None,
+        };
+
+        Ok(PortBinding {
+            name: long.name,
+            host_ip: long.host_ip,
+            published,
+            target: PortRange::from_str(&port_string(&long.target)?)?,
+            protocol: long.protocol.unwrap_or_else(|| String::from("tcp")),
+            app_protocol: long.app_protocol,
+            mode: long.mode,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_short() {
+        let port = PortBinding::parse("8080:80").unwrap();
+        assert_eq!(port.published, Some(PortRange::single(8080)));
+        assert_eq!(port.target, PortRange::single(80));
+        assert_eq!(port.protocol, "tcp");
+        assert_eq!(port.exposure(), PortExposure::AllInterfaces);
+
+        let port = PortBinding::parse("3000").unwrap();
+        assert_eq!(port.published, None);
+        assert_eq!(port.target, PortRange::single(3000));
+
+        // An empty host port (`${WEB_PORT}:80` with `WEB_PORT` unset) is
+        // assigned by the engine
+        let port = PortBinding::parse(":80").unwrap();
+        assert_eq!(port.published, None);
+        assert_eq!(port.target, PortRange::single(80));
+        assert_eq!(port.exposure(), PortExposure::AllInterfaces);
+    }
+
+    #[test]
+    fn parse_host_ip() {
+        let port = PortBinding::parse("127.0.0.1:8080:80/udp").unwrap();
+        assert_eq!(port.host_ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(port.protocol, "udp");
+        assert_eq!(port.exposure(), PortExposure::Loopback);
+
+        let port = PortBinding::parse("127.0.0.1::5000").unwrap();
+        assert_eq!(port.published, None);
+        assert_eq!(port.target, PortRange::single(5000));
+
+        let port = PortBinding::parse("[::1]:6000:6000").unwrap();
+        assert_eq!(port.host_ip.as_deref(), Some("::1"));
+        assert_eq!(port.exposure(), PortExposure::Loopback);
+
+        let port = PortBinding::parse("10.0.0.5:443:443").unwrap();
+        assert_eq!(
+            port.exposure(),
+            PortExposure::Interface(String::from("10.0.0.5"))
+        );
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let port = PortBinding::parse("9090-9091:8080-8081").unwrap();
+        assert_eq!(
+            port.published,
+            Some(PortRange {
+                start: 9090,
+                end: 9091
+            })
+        );
+        assert_eq!(
+            port.target,
+            PortRange {
+                start: 8080,
+                end: 8081
+            }
+        );
+
+        assert!(PortBinding::parse("9091-9090:80").is_err());
+        assert!(PortBinding::parse("http:80").is_err());
+    }
+
+    #[test]
+    fn parse_long() {
+        let data = "- target: 80\n  published: \"8080\"\n  host_ip: 0.0.0.0\n  protocol: tcp\n  mode: host\n- 443\n- \"127.0.0.1:53:53/udp\"\n";
+        let ports: Vec<PortBinding> = serde_yaml::from_str(data).unwrap();
+
+        assert_eq!(ports.len(), 3);
+        assert_eq!(ports[0].published, Some(PortRange::single(8080)));
+        assert_eq!(ports[0].mode.as_deref(), Some("host"));
+        assert_eq!(ports[0].exposure(), PortExposure::AllInterfaces);
+        assert_eq!(ports[1].target, PortRange::single(443));
+        assert_eq!(ports[2].exposure(), PortExposure::Loopback);
+    }
+}
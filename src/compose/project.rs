@@ -0,0 +1,125 @@
+//! Compose projects made of a base file, an optional override file and
+//! environment overlays (`docker-compose.prod.yml`)
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Canonical compose file names, in the order the Compose CLI picks them
+pub const COMPOSE_FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// Base name and overlay name of a compose file
+/// (`docker-compose.prod.yml` -> (`docker-compose`, Some(`prod`)))
+fn compose_name(path: &Path) -> Option<(&str, Option<&str>)> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name
+        .strip_suffix(".yml")
+        .or_else(|| name.strip_suffix(".yaml"))?;
+
+    let (base, overlay) = match stem.split_once('.') {
+        Some((base, overlay)) => (base, Some(overlay)),
+        None => (stem, None),
+    };
+
+    match base {
+        "compose" | "docker-compose" => Some((base, overlay)),
+        _ => None,
+    }
+}
+
+/// File has a compose file name, canonical or an overlay
+/// (`compose.yml`, `docker-compose.prod.yml`)
+pub fn is_compose_name(path: &Path) -> bool {
+    compose_name(path).is_some()
+}
+
+/// Group YAML files into compose projects.
+///
+/// Each directory with a canonical compose file becomes a project of the
+/// base file and its `*.override.*` file. Every other overlay in the
+/// directory is merged on top of the base file as its own project. All other
+/// files are their own single file project.
+pub fn group(paths: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut directories: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        let parent = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        directories.entry(parent).or_default().push(path);
+    }
+
+    let mut projects: Vec<Vec<PathBuf>> = Vec::new();
+
+    for (_, mut files) in directories {
+        files.sort();
+
+        let base = COMPOSE_FILES.iter().find_map(|name| {
+            files
+                .iter()
+                .find(|f| f.file_name().and_then(|n| n.to_str()) == Some(name))
+                .cloned()
+        });
+
+        let base = match base {
+            Some(b) => b,
+            None => {
+                projects.extend(files.into_iter().map(|f| vec![f]));
+                continue;
+            }
+        };
+        let base_name = compose_name(&base)
+            .map(|(b, _)| b.to_string())
+            .unwrap_or_default();
+
+        let mut project = vec![base.clone()];
+        let mut overlays: Vec<Vec<PathBuf>> = Vec::new();
+
+        for file in files {
+            if file == base {
+                continue;
+            }
+            match compose_name(&file) {
+                Some((b, Some("override"))) if b == base_name => project.push(file),
+                Some((b, Some(_))) if b == base_name => overlays.push(vec![base.clone(), file]),
+                _ => projects.push(vec![file]),
+            }
+        }
+
+        projects.push(project);
+        projects.extend(overlays);
+    }
+
+    projects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_projects() {
+        let paths = vec![
+            PathBuf::from("/srv/app/docker-compose.yml"),
+            PathBuf::from("/srv/app/docker-compose.override.yml"),
+            PathBuf::from("/srv/app/docker-compose.prod.yml"),
+            PathBuf::from("/srv/app/other.yml"),
+            PathBuf::from("/srv/web/compose.override.yaml"),
+        ];
+        let projects = group(paths);
+
+        assert_eq!(projects.len(), 4);
+        assert!(projects.contains(&vec![
+            PathBuf::from("/srv/app/docker-compose.yml"),
+            PathBuf::from("/srv/app/docker-compose.override.yml"),
+        ]));
+        assert!(projects.contains(&vec![
+            PathBuf::from("/srv/app/docker-compose.yml"),
+            PathBuf::from("/srv/app/docker-compose.prod.yml"),
+        ]));
+        assert!(projects.contains(&vec![PathBuf::from("/srv/app/other.yml")]));
+        assert!(projects.contains(&vec![PathBuf::from("/srv/web/compose.override.yaml")]));
+    }
+}
@@ -0,0 +1,210 @@
+//! Validation of compose files against the compose-spec JSON schema
+//!
+//! https://github.com/compose-spec/compose-spec/blob/main/schema/compose-spec.json
+use jsonschema::{error::ValidationErrorKind, paths::PathChunk, JSONSchema};
+use log::debug;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Embedded compose-spec schema
+const COMPOSE_SPEC: &str = include_str!("compose-spec.json");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Part of a compose file that does not match the compose-spec schema
+pub struct SchemaViolation {
+    /// Compose key of the invalid (or unknown) value
+    pub key: String,
+    /// Description of the violation
+    pub message: String,
+    /// Allowed key that an unknown key was probably meant to be
+    pub suggestion: Option<String>,
+}
+
+fn schema() -> &'static (Value, JSONSchema) {
+    static SCHEMA: OnceLock<(Value, JSONSchema)> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        let value: Value =
+            serde_json::from_str(COMPOSE_SPEC).expect("embedded compose-spec schema is valid JSON");
+        let compiled = JSONSchema::compile(&value).expect("embedded compose-spec schema compiles");
+        (value, compiled)
+    })
+}
+
+/// Validate a compose document against the compose-spec schema
+pub fn validate(raw: &serde_yaml::Value) -> Vec<SchemaViolation> {
+    let instance = match serde_json::to_value(raw) {
+        Ok(instance) => instance,
+        Err(err) => {
+            debug!("Unable to convert compose file for schema validation: {err}");
+            return Vec::new();
+        }
+    };
+    let (spec, compiled) = schema();
+
+    let errors = match compiled.validate(&instance) {
+        Ok(()) => return Vec::new(),
+        Err(errors) => errors,
+    };
+
+    let mut violations = Vec::new();
+    for error in errors {
+        let key = compose_key(error.instance_path.iter());
+
+        match &error.kind {
+            ValidationErrorKind::AdditionalProperties { unexpected } => {
+                let allowed = properties(spec, error.schema_path.iter());
+                for name in unexpected {
+                    let child = match key.is_empty() {
+                        true => name.clone(),
+                        false => format!("{key}.{name}"),
+                    };
+                    violations.push(SchemaViolation {
+                        key: child,
+                        message: format!("Unknown key `{name}`{}", within(&key)),
+                        suggestion: suggest(name, &allowed),
+                    });
+                }
+            }
+            _ => violations.push(SchemaViolation {
+                message: format!("Invalid value for `{key}`: {error}"),
+                key,
+                suggestion: None,
+            }),
+        }
+    }
+    violations.sort_by(|a, b| a.key.cmp(&b.key));
+    violations
+}
+
+/// Compose key (`services.web.ports[0]`) of a JSON pointer
+fn compose_key<'a>(chunks: impl Iterator<Item = &'a PathChunk>) -> String {
+    let mut key = String::new();
+    for chunk in chunks {
+        match chunk {
+            PathChunk::Property(name) => {
+                if !key.is_empty() {
+                    key.push('.');
+                }
+                key.push_str(name);
+            }
+            PathChunk::Index(index) => key.push_str(&format!("[{index}]")),
+            PathChunk::Keyword(_) => {}
+        }
+    }
+    key
+}
+
+/// Where a key is, such as "in service `web`"
+fn within(parent: &str) -> String {
+    let parts: Vec<&str> = parent.splitn(3, '.').collect();
+    match parts.as_slice() {
+        [] | [""] => String::from(" at the top level"),
+        ["services", service] => format!(" in service `{service}`"),
+        _ => format!(" in `{parent}`"),
+    }
+}
+
+/// Property names allowed by the schema object that contains the keyword at
+/// `schema_path`, following `$ref`s
+fn properties<'a>(spec: &Value, schema_path: impl Iterator<Item = &'a PathChunk>) -> Vec<String> {
+    let chunks: Vec<&PathChunk> = schema_path.collect();
+    let mut node = resolve(spec, spec);
+
+    // The last chunk is the `additionalProperties` keyword itself
+    for chunk in chunks.iter().take(chunks.len().saturating_sub(1)) {
+        let next = match chunk {
+            PathChunk::Property(name) => node.get(name.as_ref()),
+            PathChunk::Keyword(keyword) => node.get(*keyword),
+            PathChunk::Index(index) => node.get(*index),
+        };
+        node = match next {
+            Some(next) => resolve(spec, next),
+            None => return Vec::new(),
+        };
+    }
+
+    node.get("properties")
+        .and_then(Value::as_object)
+        .map(|props| props.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Schema node a `$ref` points to (or the node itself)
+fn resolve<'a>(spec: &'a Value, node: &'a Value) -> &'a Value {
+    node.get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix('#'))
+        .and_then(|pointer| spec.pointer(pointer))
+        .unwrap_or(node)
+}
+
+/// Closest allowed name to a misspelled key, treating `-` and `_` as equal
+fn suggest(name: &str, allowed: &[String]) -> Option<String> {
+    let normalised = name.to_lowercase().replace('-', "_");
+    let limit = (normalised.len() / 3).max(1);
+
+    allowed
+        .iter()
+        .map(|candidate| (strsim::levenshtein(&normalised, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violations(data: &str) -> Vec<SchemaViolation> {
+        validate(&serde_yaml::from_str(data).unwrap())
+    }
+
+    #[test]
+    fn valid() {
+        let data = "version: '3'\nx-common: &common\n  restart: always\nservices:\n  web:\n    <<: *common\n    image: nginx\n    privileged: true\n    ports:\n      - 80:80\n      - target: 443\n    environment:\n      KEY: value\n    x-note: ok\nvolumes:\n  data:\n";
+        let mut raw: serde_yaml::Value = serde_yaml::from_str(data).unwrap();
+        raw.apply_merge().unwrap();
+        assert_eq!(validate(&raw), Vec::new());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let violations =
+            violations("services:\n  web:\n    image: nginx\n    privilaged: true\n    security-opt: []\nservice: {}\n");
+
+        assert_eq!(violations.len(), 3);
+        assert_eq!(violations[0].key, "service");
+        assert_eq!(
+            violations[0].message,
+            "Unknown key `service` at the top level"
+        );
+        assert_eq!(violations[0].suggestion.as_deref(), Some("services"));
+        assert_eq!(violations[1].key, "services.web.privilaged");
+        assert_eq!(
+            violations[1].message,
+            "Unknown key `privilaged` in service `web`"
+        );
+        assert_eq!(violations[1].suggestion.as_deref(), Some("privileged"));
+        assert_eq!(violations[2].key, "services.web.security-opt");
+        assert_eq!(violations[2].suggestion.as_deref(), Some("security_opt"));
+    }
+
+    #[test]
+    fn invalid_values() {
+        let violations = violations(
+            "services:\n  web:\n    image: nginx\n    cap_add: NET_ADMIN\n    cgroup: shared\n    ports:\n      - [80]\n",
+        );
+
+        let keys: Vec<&str> = violations.iter().map(|v| v.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "services.web.cap_add",
+                "services.web.cgroup",
+                "services.web.ports[0]"
+            ]
+        );
+        assert!(violations[0].message.contains("not of type \"array\""));
+        assert!(violations[1].message.contains("is not one of"));
+    }
+}
@@ -1,8 +1,17 @@
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+};
 
-use crate::containers::ContainerImage;
+use crate::{
+    compose::{PortBinding, PortExposure, ServiceVolume},
+    containers::ContainerImage,
+};
+
+/// Unknown and extension (`x-*`) keys kept as raw YAML values
+pub type Extensions = HashMap<String, serde_yaml::Value>;
 
 #[derive(Debug, Serialize, Deserialize)]
 /// Based on the current spec
@@ -12,8 +21,48 @@ pub struct ComposeSpec {
     pub version: Option<String>,
     // Name of the compose project
     pub name: Option<String>,
+    /// Other compose files to include in the project
+    pub include: Option<Vec<Include>>,
     /// Compose Services
+    #[serde(default)]
     pub services: HashMap<String, Service>,
+    /// Top-level networks
+    pub networks: Option<HashMap<String, Option<NetworkDefinition>>>,
+    /// Top-level named volumes
+    pub volumes: Option<HashMap<String, Option<VolumeDefinition>>>,
+    /// Top-level secrets
+    pub secrets: Option<HashMap<String, SecretDefinition>>,
+    /// Top-level configs
+    pub configs: Option<HashMap<String, ConfigDefinition>>,
+    /// Top-level AI models
+    pub models: Option<HashMap<String, ModelDefinition>>,
+    /// Unknown and `x-*` extension keys
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+impl ComposeSpec {
+    /// Services enabled by the active profiles, including the services they
+    /// depend on (like Compose, dependencies are enabled with them)
+    pub fn enabled_services(&self, profiles: &[String]) -> BTreeSet<String> {
+        let mut enabled = BTreeSet::new();
+        let mut pending: Vec<&String> = self
+            .services
+            .iter()
+            .filter(|(_, service)| service.is_enabled(profiles))
+            .map(|(name, _)| name)
+            .collect();
+
+        while let Some(name) = pending.pop() {
+            if !enabled.insert(name.clone()) {
+                continue;
+            }
+            if let Some(service) = self.services.get(name) {
+                pending.extend(service.dependencies());
+            }
+        }
+        enabled
+    }
 }
 
 impl Display for ComposeSpec {
@@ -35,20 +84,69 @@ impl Display for ComposeSpec {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Service {
+    /// Metadata annotations for the container
+    pub annotations: Option<ListOrHashMap>,
+    /// Attach to the service output
+    pub attach: Option<bool>,
+
     // Using external container
     pub image: Option<String>,
 
     // Manually building container
     pub build: Option<StringOrBuild>,
 
+    /// Block IO configuration
+    pub blkio_config: Option<BlkioConfig>,
+
+    /// CPU limits
+    pub cpu_count: Option<StringOrNumber>,
+    pub cpu_percent: Option<StringOrNumber>,
+    pub cpu_shares: Option<StringOrNumber>,
+    pub cpu_quota: Option<StringOrNumber>,
+    pub cpu_period: Option<StringOrNumber>,
+    pub cpu_rt_period: Option<StringOrNumber>,
+    pub cpu_rt_runtime: Option<StringOrNumber>,
+    pub cpus: Option<StringOrNumber>,
+    pub cpuset: Option<String>,
+
+    /// Windows credential spec
+    pub credential_spec: Option<CredentialSpec>,
+
     // Container name
     pub container_name: Option<String>,
 
+    /// Services this service depends on
+    pub depends_on: Option<DependsOn>,
+
+    /// Deployment configuration
+    pub deploy: Option<Deploy>,
+
+    /// Development (watch) configuration
+    pub develop: Option<Develop>,
+
+    /// Device cgroup rules
+    pub device_cgroup_rules: Option<Vec<String>>,
+
+    /// Host devices mapped into the container
+    pub devices: Option<Vec<StringOrDevice>>,
+
     // DNS server settings
     pub dns: Option<StringOrList>,
+    pub dns_opt: Option<Vec<String>>,
+    pub dns_search: Option<StringOrList>,
+    pub domainname: Option<String>,
+
+    /// Override the image entrypoint
+    pub entrypoint: Option<StringOrList>,
+
+    /// Override the image command
+    pub command: Option<StringOrList>,
 
     // Environment files
-    pub env_file: Option<StringOrList>,
+    pub env_file: Option<EnvFiles>,
+
+    /// Label files
+    pub label_file: Option<StringOrList>,
 
     // Environment
     pub environment: Option<ListOrHashMap>,
@@ -56,29 +154,153 @@ pub struct Service {
     // Exposed ports
     pub expose: Option<Vec<StringOrNumber>>,
 
-    // Ports
-    pub ports: Option<Vec<StringOrNumber>>,
+    /// Extend another service
+    pub extends: Option<Extends>,
 
-    // Volumes
-    pub volumes: Option<Vec<String>>,
+    pub external_links: Option<Vec<String>>,
+
+    /// Extra `/etc/hosts` entries
+    pub extra_hosts: Option<ListOrHashMap>,
+
+    /// GPU devices
+    pub gpus: Option<Gpus>,
+
+    /// Additional groups for the container user
+    pub group_add: Option<Vec<StringOrNumber>>,
+
+    /// Container health check
+    pub healthcheck: Option<Healthcheck>,
+
+    pub hostname: Option<String>,
+
+    /// Run an init process inside the container
+    pub init: Option<bool>,
+
+    /// IPC isolation mode
+    pub ipc: Option<String>,
+
+    pub isolation: Option<String>,
 
     /// Compose Service labels
     pub labels: Option<ListOrHashMap>,
 
-    /// Compose security options
-    pub security_opt: Option<Vec<String>>,
+    pub links: Option<Vec<String>>,
+
+    /// Logging configuration
+    pub logging: Option<Logging>,
+
+    pub mac_address: Option<String>,
+
+    /// Memory limits
+    pub mem_limit: Option<StringOrNumber>,
+    pub mem_reservation: Option<StringOrNumber>,
+    pub mem_swappiness: Option<StringOrNumber>,
+    pub memswap_limit: Option<StringOrNumber>,
+
+    /// AI models used by the service
+    pub models: Option<ServiceModels>,
+
+    /// Network mode (`host`, `none`, `service:<name>`, ...)
+    pub network_mode: Option<String>,
+
+    /// Networks the service is attached to
+    pub networks: Option<ServiceNetworks>,
+
+    pub oom_kill_disable: Option<bool>,
+    pub oom_score_adj: Option<StringOrNumber>,
+
+    /// PID namespace mode
+    pub pid: Option<String>,
+    pub pids_limit: Option<StringOrNumber>,
+
+    pub platform: Option<String>,
+
+    // Ports
+    pub ports: Option<Vec<PortBinding>>,
+
+    /// Lifecycle hooks
+    pub post_start: Option<Vec<ServiceHook>>,
+    pub pre_stop: Option<Vec<ServiceHook>>,
+
+    /// Compose service is running as privileged
+    pub privileged: Option<bool>,
+
+    /// Profiles the service is enabled for
+    pub profiles: Option<Vec<String>>,
+
+    /// Service provided by an external provider
+    pub provider: Option<Provider>,
+
+    pub pull_policy: Option<String>,
+    pub pull_refresh_after: Option<String>,
+
+    /// Read-only root filesystem
+    pub read_only: Option<bool>,
 
     /// Compose service restart policy
     pub restart: Option<String>,
 
+    pub runtime: Option<String>,
+
+    pub scale: Option<StringOrNumber>,
+
+    /// Secrets granted to the service
+    pub secrets: Option<Vec<ServiceResource>>,
+
+    /// Configs granted to the service
+    pub configs: Option<Vec<ServiceResource>>,
+
+    /// Compose security options
+    pub security_opt: Option<Vec<String>>,
+
+    pub shm_size: Option<StringOrNumber>,
+
+    pub stdin_open: Option<bool>,
+
+    pub stop_grace_period: Option<String>,
+    pub stop_signal: Option<String>,
+
+    pub storage_opt: Option<HashMap<String, serde_yaml::Value>>,
+
     /// Compose sysctls
     pub sysctls: Option<ListOrHashMap>,
 
     /// Compose cap_add
     pub cap_add: Option<Vec<String>>,
 
-    /// Compose service is running as privileged
-    pub privileged: Option<bool>,
+    /// Compose cap_drop
+    pub cap_drop: Option<Vec<String>>,
+
+    /// Temporary file systems
+    pub tmpfs: Option<StringOrList>,
+
+    pub tty: Option<bool>,
+
+    /// Resource limits
+    pub ulimits: Option<HashMap<String, Ulimit>>,
+
+    /// Expose the Docker API socket to the container
+    pub use_api_socket: Option<bool>,
+
+    /// User the container process runs as
+    pub user: Option<String>,
+
+    /// User namespace mode
+    pub userns_mode: Option<String>,
+
+    /// UTS namespace mode
+    pub uts: Option<String>,
+
+    // Volumes
+    pub volumes: Option<Vec<ServiceVolume>>,
+
+    pub volumes_from: Option<Vec<String>>,
+
+    pub working_dir: Option<String>,
+
+    /// Unknown and `x-*` extension keys
+    #[serde(flatten)]
+    pub extensions: Extensions,
 }
 
 impl Service {
@@ -88,6 +310,39 @@ impl Service {
             None => Err(anyhow!("Failed to parse `image`")),
         }
     }
+
+    /// Widest host exposure of the service's ports
+    pub fn exposure(&self) -> PortExposure {
+        if self.network_mode.as_deref() == Some("host") {
+            return PortExposure::AllInterfaces;
+        }
+        self.ports
+            .iter()
+            .flatten()
+            .map(|p| p.exposure())
+            .min()
+            .unwrap_or(PortExposure::NotPublished)
+    }
+
+    /// Names of the services in `depends_on`
+    pub fn dependencies(&self) -> Vec<&String> {
+        match &self.depends_on {
+            Some(DependsOn::List(names)) => names.iter().collect(),
+            Some(DependsOn::Map(names)) => names.keys().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Service is enabled when one of its profiles is active, services
+    /// without profiles are always enabled
+    pub fn is_enabled(&self, profiles: &[String]) -> bool {
+        match &self.profiles {
+            Some(service_profiles) if !service_profiles.is_empty() => {
+                service_profiles.iter().any(|p| profiles.contains(p))
+            }
+            _ => true,
+        }
+    }
 }
 
 impl Display for Service {
@@ -106,13 +361,433 @@ impl Display for Service {
 pub struct Build {
     pub context: Option<String>,
     pub dockerfile: Option<String>,
+    pub dockerfile_inline: Option<String>,
+    pub entitlements: Option<Vec<String>>,
+    pub args: Option<ListOrHashMap>,
+    pub ssh: Option<ListOrHashMap>,
+    pub labels: Option<ListOrHashMap>,
+    pub cache_from: Option<Vec<String>>,
+    pub cache_to: Option<Vec<String>>,
+    pub no_cache: Option<bool>,
+    pub additional_contexts: Option<ListOrHashMap>,
+    pub network: Option<String>,
+    pub provenance: Option<serde_yaml::Value>,
+    pub sbom: Option<serde_yaml::Value>,
+    pub pull: Option<bool>,
+    pub target: Option<String>,
+    pub shm_size: Option<StringOrNumber>,
+    pub extra_hosts: Option<ListOrHashMap>,
+    pub isolation: Option<String>,
+    pub privileged: Option<bool>,
+    pub secrets: Option<Vec<ServiceResource>>,
+    pub tags: Option<Vec<String>>,
+    pub ulimits: Option<HashMap<String, Ulimit>>,
+    pub platforms: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum StringOrBuild {
-    Build(Build),
+    Build(Box<Build>),
+    Str(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlkioConfig {
+    pub device_read_bps: Option<Vec<BlkioLimit>>,
+    pub device_read_iops: Option<Vec<BlkioLimit>>,
+    pub device_write_bps: Option<Vec<BlkioLimit>>,
+    pub device_write_iops: Option<Vec<BlkioLimit>>,
+    pub weight: Option<StringOrNumber>,
+    pub weight_device: Option<Vec<BlkioWeight>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlkioLimit {
+    pub path: Option<String>,
+    pub rate: Option<StringOrNumber>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlkioWeight {
+    pub path: Option<String>,
+    pub weight: Option<StringOrNumber>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialSpec {
+    pub config: Option<String>,
+    pub file: Option<String>,
+    pub registry: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DependsOn {
+    List(Vec<String>),
+    Map(HashMap<String, ServiceDependency>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceDependency {
+    /// `service_started`, `service_healthy` or `service_completed_successfully`
+    pub condition: Option<String>,
+    pub restart: Option<bool>,
+    pub required: Option<bool>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Deploy {
+    pub mode: Option<String>,
+    pub endpoint_mode: Option<String>,
+    pub replicas: Option<StringOrNumber>,
+    pub labels: Option<ListOrHashMap>,
+    pub rollback_config: Option<UpdateConfig>,
+    pub update_config: Option<UpdateConfig>,
+    pub resources: Option<Resources>,
+    pub restart_policy: Option<RestartPolicy>,
+    pub placement: Option<Placement>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    pub parallelism: Option<StringOrNumber>,
+    pub delay: Option<String>,
+    pub failure_action: Option<String>,
+    pub monitor: Option<String>,
+    pub max_failure_ratio: Option<StringOrNumber>,
+    pub order: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resources {
+    pub limits: Option<ResourceLimits>,
+    pub reservations: Option<ResourceLimits>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    pub cpus: Option<StringOrNumber>,
+    pub memory: Option<StringOrNumber>,
+    pub pids: Option<StringOrNumber>,
+    pub generic_resources: Option<Vec<serde_yaml::Value>>,
+    pub devices: Option<Vec<DeviceRequest>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceRequest {
+    pub capabilities: Option<Vec<String>>,
+    pub count: Option<StringOrNumber>,
+    pub device_ids: Option<Vec<String>>,
+    pub driver: Option<String>,
+    pub options: Option<ListOrHashMap>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    pub condition: Option<String>,
+    pub delay: Option<String>,
+    pub max_attempts: Option<StringOrNumber>,
+    pub window: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Placement {
+    pub constraints: Option<Vec<String>>,
+    pub preferences: Option<Vec<HashMap<String, String>>>,
+    pub max_replicas_per_node: Option<StringOrNumber>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Develop {
+    pub watch: Option<Vec<Watch>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Watch {
+    pub path: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub ignore: Option<Vec<String>>,
+    pub include: Option<Vec<String>>,
+    pub exec: Option<ServiceHook>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StringOrDevice {
     Str(String),
+    Device(Device),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Device {
+    pub source: String,
+    pub target: Option<String>,
+    pub permissions: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvFiles {
+    Str(String),
+    List(Vec<EnvFile>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvFile {
+    Str(String),
+    Long {
+        path: String,
+        required: Option<bool>,
+        format: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Extends {
+    Str(String),
+    Service {
+        service: String,
+        file: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Gpus {
+    Str(String),
+    List(Vec<DeviceRequest>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Healthcheck {
+    pub disable: Option<bool>,
+    pub interval: Option<String>,
+    pub retries: Option<StringOrNumber>,
+    pub test: Option<StringOrList>,
+    pub timeout: Option<String>,
+    pub start_period: Option<String>,
+    pub start_interval: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Logging {
+    pub driver: Option<String>,
+    pub options: Option<HashMap<String, StringOrNumber>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServiceModels {
+    List(Vec<String>),
+    Map(HashMap<String, Option<ServiceModel>>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceModel {
+    pub endpoint_var: Option<String>,
+    pub model_var: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServiceNetworks {
+    List(Vec<String>),
+    Map(HashMap<String, Option<ServiceNetwork>>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceNetwork {
+    pub aliases: Option<Vec<String>>,
+    pub interface_name: Option<String>,
+    pub ipv4_address: Option<String>,
+    pub ipv6_address: Option<String>,
+    pub link_local_ips: Option<Vec<String>>,
+    pub mac_address: Option<String>,
+    pub driver_opts: Option<HashMap<String, StringOrNumber>>,
+    pub gw_priority: Option<StringOrNumber>,
+    pub priority: Option<StringOrNumber>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceHook {
+    pub command: StringOrList,
+    pub user: Option<String>,
+    pub privileged: Option<bool>,
+    pub working_dir: Option<String>,
+    pub environment: Option<ListOrHashMap>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Provider {
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    pub options: Option<HashMap<String, serde_yaml::Value>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServiceResource {
+    Str(String),
+    Long {
+        source: String,
+        target: Option<String>,
+        uid: Option<StringOrNumber>,
+        gid: Option<StringOrNumber>,
+        mode: Option<StringOrNumber>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Ulimit {
+    Single(StringOrNumber),
+    Limits {
+        soft: StringOrNumber,
+        hard: StringOrNumber,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Include {
+    Str(String),
+    Long {
+        path: StringOrList,
+        env_file: Option<StringOrList>,
+        project_directory: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkDefinition {
+    pub name: Option<String>,
+    pub driver: Option<String>,
+    pub driver_opts: Option<HashMap<String, StringOrNumber>>,
+    pub ipam: Option<Ipam>,
+    pub external: Option<External>,
+    pub internal: Option<bool>,
+    pub enable_ipv4: Option<bool>,
+    pub enable_ipv6: Option<bool>,
+    pub attachable: Option<bool>,
+    pub labels: Option<ListOrHashMap>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ipam {
+    pub driver: Option<String>,
+    pub config: Option<Vec<IpamConfig>>,
+    pub options: Option<HashMap<String, String>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IpamConfig {
+    pub subnet: Option<String>,
+    pub ip_range: Option<String>,
+    pub gateway: Option<String>,
+    pub aux_addresses: Option<HashMap<String, String>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeDefinition {
+    pub name: Option<String>,
+    pub driver: Option<String>,
+    pub driver_opts: Option<HashMap<String, StringOrNumber>>,
+    pub external: Option<External>,
+    pub labels: Option<ListOrHashMap>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecretDefinition {
+    pub name: Option<String>,
+    pub environment: Option<String>,
+    pub file: Option<String>,
+    pub external: Option<External>,
+    pub labels: Option<ListOrHashMap>,
+    pub driver: Option<String>,
+    pub driver_opts: Option<HashMap<String, StringOrNumber>>,
+    pub template_driver: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigDefinition {
+    pub name: Option<String>,
+    pub content: Option<String>,
+    pub environment: Option<String>,
+    pub file: Option<String>,
+    pub external: Option<External>,
+    pub labels: Option<ListOrHashMap>,
+    pub template_driver: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModelDefinition {
+    pub name: Option<String>,
+    pub model: String,
+    pub context_size: Option<StringOrNumber>,
+    pub runtime_flags: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extensions: Extensions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum External {
+    Bool(bool),
+    Named { name: Option<String> },
 }
 
 // Serde Generic Enums
@@ -121,6 +796,9 @@ pub enum StringOrBuild {
 #[serde(untagged)]
 pub enum StringOrNumber {
     Num(usize),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
     Str(String),
     None,
 }
@@ -138,3 +816,89 @@ pub enum ListOrHashMap {
     Hash(HashMap<String, StringOrNumber>),
     Vec(Vec<StringOrNumber>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_full_service() {
+        let data = r#"
+name: example
+services:
+  web:
+    image: nginx:1.25
+    user: "1000:1000"
+    read_only: true
+    network_mode: host
+    pid: host
+    ipc: shareable
+    cap_drop: [ALL]
+    devices:
+      - /dev/ttyUSB0:/dev/ttyUSB0
+      - source: /dev/sda
+        target: /dev/xvda
+    tmpfs: /run
+    ulimits:
+      nproc: 65535
+      nofile:
+        soft: 20000
+        hard: 40000
+    healthcheck:
+      test: ["CMD", "curl", "-f", "http://localhost"]
+      interval: 30s
+    deploy:
+      resources:
+        limits:
+          cpus: 0.5
+          memory: 50M
+    logging:
+      driver: json-file
+    environment:
+      DEBUG: true
+    depends_on:
+      db:
+        condition: service_healthy
+    profiles: [debug]
+    secrets:
+      - token
+      - source: token
+        target: /run/secrets/api_token
+        uid: 103
+        gid: "103"
+        mode: 0440
+    x-custom: value
+    unknown_key: 1
+networks:
+  backend:
+    internal: true
+secrets:
+  token:
+    file: ./token.txt
+x-common: &common
+  restart: always
+"#;
+        let spec: ComposeSpec = serde_yaml::from_str(data).unwrap();
+        let web = spec.services.get("web").unwrap();
+
+        assert_eq!(web.user.as_deref(), Some("1000:1000"));
+        assert_eq!(web.read_only, Some(true));
+        assert_eq!(web.network_mode.as_deref(), Some("host"));
+        assert_eq!(web.devices.as_ref().unwrap().len(), 2);
+        assert!(matches!(web.depends_on, Some(DependsOn::Map(_))));
+        assert!(matches!(
+            web.secrets.as_ref().unwrap()[1],
+            ServiceResource::Long {
+                uid: Some(StringOrNumber::Num(103)),
+                gid: Some(StringOrNumber::Str(_)),
+                ..
+            }
+        ));
+        assert!(web.extensions.contains_key("x-custom"));
+        assert!(web.extensions.contains_key("unknown_key"));
+
+        assert!(spec.networks.unwrap().contains_key("backend"));
+        assert!(spec.secrets.unwrap().contains_key("token"));
+        assert!(spec.extensions.contains_key("x-common"));
+    }
+}
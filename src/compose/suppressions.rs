@@ -0,0 +1,145 @@
+//! `# quibble-ignore` comments that suppress alerts
+//!
+//! ```yaml
+//! services:
+//!   traefik:
+//!     volumes:
+//!       # quibble-ignore: DOCKER_SOCKET -- Traefik needs the socket for discovery
+//!       - /var/run/docker.sock:/var/run/docker.sock:ro
+//!     privileged: true # quibble-ignore: PRIVILEGED_CONTAINER
+//! ```
+//!
+//! `# quibble-ignore-file: ID -- reason` suppresses an alert in the whole file.
+
+/// Comment suppressing alerts on a line
+const IGNORE: &str = "quibble-ignore";
+/// Comment suppressing alerts in the whole file
+const IGNORE_FILE: &str = "quibble-ignore-file";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `quibble-ignore` comment in a compose file
+pub struct IgnoreComment {
+    /// Zero based line the comment applies to, `None` for the whole file
+    pub line: Option<i32>,
+    /// Rule names or alert IDs that are suppressed
+    pub ids: Vec<String>,
+    /// Reason given after `--`
+    pub justification: Option<String>,
+}
+
+impl IgnoreComment {
+    /// Comment suppresses a rule name or alert ID
+    pub fn matches(&self, id: &str) -> bool {
+        self.ids.iter().any(|i| i == id)
+    }
+}
+
+/// Parse the `quibble-ignore` comments of a file
+pub fn parse(data: &str) -> Vec<IgnoreComment> {
+    let lines: Vec<&str> = data.lines().collect();
+    let mut comments = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let (before, comment) = match split_comment(line) {
+            Some(split) => split,
+            None => continue,
+        };
+        let (file, rest) = match comment.strip_prefix(IGNORE_FILE) {
+            Some(rest) => (true, rest),
+            None => match comment.strip_prefix(IGNORE) {
+                Some(rest) => (false, rest),
+                None => continue,
+            },
+        };
+        if !(rest.is_empty() || rest.starts_with([':', ' '])) {
+            continue;
+        }
+
+        let rest = rest.trim_start_matches(':');
+        let (ids, justification) = match rest.split_once("--") {
+            Some((ids, reason)) => (ids, Some(reason.trim().to_string())),
+            None => (rest, None),
+        };
+
+        let line = match (file, before.trim().is_empty()) {
+            (true, _) => None,
+            // Trailing comment
+            (false, false) => Some(index as i32),
+            // Comment above the line, skipping blank and comment lines
+            (false, true) => lines
+                .iter()
+                .enumerate()
+                .skip(index + 1)
+                .find(|(_, l)| {
+                    let l = l.trim();
+                    !l.is_empty() && !l.starts_with('#')
+                })
+                .map(|(i, _)| i as i32),
+        };
+        if !file && line.is_none() {
+            continue;
+        }
+
+        comments.push(IgnoreComment {
+            line,
+            ids: ids
+                .split([',', ' '])
+                .map(|id| id.trim())
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect(),
+            justification: justification.filter(|j| !j.is_empty()),
+        });
+    }
+
+    comments
+}
+
+/// Split a line into its content and the text of a YAML comment (`#` at the
+/// start of the line or after whitespace)
+fn split_comment(line: &str) -> Option<(&str, &str)> {
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return Some((&line[..index], line[index + 1..].trim()));
+        }
+        previous = c;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_comments() {
+        let comments = parse(
+            "# quibble-ignore-file: IMAGE_TAG_LATEST -- pinned by renovate\nservices:\n  traefik:\n    image: traefik:latest\n    volumes:\n      # quibble-ignore: DOCKER_SOCKET -- needed for discovery\n\n      - /var/run/docker.sock:/var/run/docker.sock\n    privileged: true # quibble-ignore: PRIVILEGED_CONTAINER, SECURITY_OPTS\n    command: echo '#quibble-ignore: nope'\n",
+        );
+
+        assert_eq!(
+            comments,
+            vec![
+                IgnoreComment {
+                    line: None,
+                    ids: vec![String::from("IMAGE_TAG_LATEST")],
+                    justification: Some(String::from("pinned by renovate")),
+                },
+                IgnoreComment {
+                    line: Some(7),
+                    ids: vec![String::from("DOCKER_SOCKET")],
+                    justification: Some(String::from("needed for discovery")),
+                },
+                IgnoreComment {
+                    line: Some(8),
+                    ids: vec![
+                        String::from("PRIVILEGED_CONTAINER"),
+                        String::from("SECURITY_OPTS")
+                    ],
+                    justification: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Mount type of a service volume
+pub enum MountType {
+    Bind,
+    #[default]
+    Volume,
+    Tmpfs,
+    Npipe,
+    Cluster,
+    Image,
+}
+
+impl FromStr for MountType {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "bind" => Ok(Self::Bind),
+            "volume" => Ok(Self::Volume),
+            "tmpfs" => Ok(Self::Tmpfs),
+            "npipe" => Ok(Self::Npipe),
+            "cluster" => Ok(Self::Cluster),
+            "image" => Ok(Self::Image),
+            _ => Err(anyhow!("Unknown volume type: `{value}`")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Access mode of a mount
+pub enum AccessMode {
+    #[default]
+    ReadWrite,
+    ReadOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Bind mount propagation
+pub enum Propagation {
+    Shared,
+    RShared,
+    Slave,
+    RSlave,
+    Private,
+    RPrivate,
+}
+
+impl FromStr for Propagation {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "shared" => Ok(Self::Shared),
+            "rshared" => Ok(Self::RShared),
+            "slave" => Ok(Self::Slave),
+            "rslave" => Ok(Self::RSlave),
+            "private" => Ok(Self::Private),
+            "rprivate" => Ok(Self::RPrivate),
+            _ => Err(anyhow!("Unknown bind propagation: `{value}`")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ServiceVolumeSyntax")]
+/// Service volume mount, parsed from either the short (`src:dst:mode`)
+/// or long (`type: bind`, `source`, `target`, ...) syntax
+pub struct ServiceVolume {
+    /// Mount type
+    #[serde(rename = "type")]
+    pub mount_type: MountType,
+    /// Host path or volume name (`None` for anonymous volumes)
+    pub source: Option<String>,
+    /// Path inside the container
+    pub target: String,
+    /// Read-only or read-write
+    pub access: AccessMode,
+    /// Bind propagation
+    pub propagation: Option<Propagation>,
+    /// SELinux relabelling (`z` or `Z`)
+    pub selinux: Option<String>,
+    /// Disable copying data from the container when a volume is created
+    pub nocopy: bool,
+    /// Mount consistency (`cached`, `delegated`, `consistent`)
+    pub consistency: Option<String>,
+}
+
+impl ServiceVolume {
+    /// Parse the short volume syntax: `[SOURCE:]TARGET[:MODE]`
+    pub fn parse(volume: &str) -> Result<Self> {
+        let parts = split_volume(volume);
+        let mut result = ServiceVolume::default();
+
+        match parts.as_slice() {
+            [target] => {
+                result.target = target.to_string();
+            }
+            [source, target] => {
+                result.source = Some(source.to_string());
+                result.target = target.to_string();
+            }
+            [source, target, mode] => {
+                result.source = Some(source.to_string());
+                result.target = target.to_string();
+                result.apply_mode(mode)?;
+            }
+            _ => return Err(anyhow!("Invalid volume syntax: `{volume}`")),
+        }
+
+        if result.target.is_empty() {
+            return Err(anyhow!("Volume has no target: `{volume}`"));
+        }
+
+        result.mount_type = match &result.source {
+            Some(source) if is_host_path(source) => MountType::Bind,
+            _ => MountType::Volume,
+        };
+
+        Ok(result)
+    }
+
+    fn apply_mode(&mut self, mode: &str) -> Result<()> {
+        for option in mode.split(',') {
+            match option {
+                "ro" => self.access = AccessMode::ReadOnly,
+                "rw" => self.access = AccessMode::ReadWrite,
+                "z" | "Z" => self.selinux = Some(option.to_string()),
+                "nocopy" => self.nocopy = true,
+                "cached" | "delegated" | "consistent" => {
+                    self.consistency = Some(option.to_string())
+                }
+                _ => self.propagation = Some(Propagation::from_str(option)?),
+            }
+        }
+        Ok(())
+    }
+
+    /// Is the mount read-only
+    pub fn is_read_only(&self) -> bool {
+        self.access == AccessMode::ReadOnly
+    }
+
+    /// Host path being bind mounted into the container
+    pub fn host_path(&self) -> Option<&str> {
+        match self.mount_type {
+            MountType::Bind => self.source.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ServiceVolume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{source}:")?;
+        }
+        write!(f, "{}", self.target)?;
+        if self.is_read_only() {
+            write!(f, ":ro")?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a short volume definition on `:`, keeping Windows drive letters
+/// (`C:\data`) together
+fn split_volume(volume: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut chars = volume.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let drive = current.len() == 1
+            && current.chars().all(|c| c.is_ascii_alphabetic())
+            && matches!(chars.peek(), Some('\\') | Some('/'));
+
+        if c == ':' && !drive {
+            parts.push(current);
+            current = String::new();
+        } else {
+            current.push(c);
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+/// Short syntax sources that are host paths rather than volume names
+fn is_host_path(source: &str) -> bool {
+    let mut chars = source.chars();
+    let drive = matches!(
+        (chars.next(), chars.next()),
+        (Some(d), Some(':')) if d.is_ascii_alphabetic()
+    );
+
+    drive
+        || source.starts_with('/')
+        || source.starts_with('.')
+        || source.starts_with('~')
+        || source.starts_with('\\')
+        || source.starts_with('$')
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ServiceVolumeSyntax {
+    Short(String),
+    Long(LongVolume),
+}
+
+#[derive(Debug, Deserialize)]
+struct LongVolume {
+    #[serde(rename = "type")]
+    mount_type: String,
+    source: Option<String>,
+    target: Option<String>,
+    read_only: Option<bool>,
+    consistency: Option<String>,
+    bind: Option<BindOptions>,
+    volume: Option<VolumeOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BindOptions {
+    propagation: Option<String>,
+    selinux: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VolumeOptions {
+    nocopy: Option<bool>,
+}
+
+impl TryFrom<ServiceVolumeSyntax> for ServiceVolume {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ServiceVolumeSyntax) -> Result<Self> {
+        let long = match value {
+            ServiceVolumeSyntax::Short(s) => return ServiceVolume::parse(&s),
+            ServiceVolumeSyntax::Long(l) => l,
+        };
+
+        let mut result = ServiceVolume {
+            mount_type: MountType::from_str(&long.mount_type)?,
+            source: long.source,
+            target: long.target.unwrap_or_default(),
+            consistency: long.consistency,
+            ..Default::default()
+        };
+        if result.target.is_empty() {
+            return Err(anyhow!("Volume has no target: `{}`", long.mount_type));
+        }
+
+        if long.read_only.unwrap_or(false) {
+            result.access = AccessMode::ReadOnly;
+        }
+        if let Some(bind) = long.bind {
+            if let Some(propagation) = bind.propagation {
+                result.propagation = Some(Propagation::from_str(&propagation)?);
+            }
+            result.selinux = bind.selinux;
+        }
+        if let Some(volume) = long.volume {
+            result.nocopy = volume.nocopy.unwrap_or(false);
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_short_bind() {
+        let volume = ServiceVolume::parse("/var/run/docker.sock:/var/run/docker.sock:ro").unwrap();
+
+        assert_eq!(volume.mount_type, MountType::Bind);
+        assert_eq!(volume.source.as_deref(), Some("/var/run/docker.sock"));
+        assert_eq!(volume.target, "/var/run/docker.sock");
+        assert!(volume.is_read_only());
+    }
+
+    #[test]
+    fn parse_short_named_and_anonymous() {
+        let named = ServiceVolume::parse("data:/var/lib/data:rw,rshared").unwrap();
+        assert_eq!(named.mount_type, MountType::Volume);
+        assert_eq!(named.source.as_deref(), Some("data"));
+        assert_eq!(named.propagation, Some(Propagation::RShared));
+        assert!(!named.is_read_only());
+
+        let anonymous = ServiceVolume::parse("/var/lib/data").unwrap();
+        assert_eq!(anonymous.mount_type, MountType::Volume);
+        assert_eq!(anonymous.source, None);
+        assert_eq!(anonymous.target, "/var/lib/data");
+    }
+
+    #[test]
+    fn parse_short_windows() {
+        let volume = ServiceVolume::parse("C:\\data:/data:ro").unwrap();
+
+        assert_eq!(volume.mount_type, MountType::Bind);
+        assert_eq!(volume.source.as_deref(), Some("C:\\data"));
+        assert_eq!(volume.target, "/data");
+    }
+
+    #[test]
+    fn parse_long() {
+        let data = "- type: bind\n  source: /etc\n  target: /host/etc\n  read_only: true\n  bind:\n    propagation: rslave\n- ./config:/config\n";
+        let volumes: Vec<ServiceVolume> = serde_yaml::from_str(data).unwrap();
+
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0].host_path(), Some("/etc"));
+        assert_eq!(volumes[0].propagation, Some(Propagation::RSlave));
+        assert!(volumes[0].is_read_only());
+        assert_eq!(volumes[1].host_path(), Some("./config"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(ServiceVolume::parse("a:b:c:d").is_err());
+        assert!(ServiceVolume::parse("data:/data:bogus").is_err());
+        assert!(serde_yaml::from_str::<ServiceVolume>("type: bind\nsource: /etc\n").is_err());
+        assert!(serde_yaml::from_str::<ServiceVolume>("type: volume\ntarget: ''\n").is_err());
+    }
+}
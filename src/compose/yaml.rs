@@ -0,0 +1,417 @@
+//! Span tracking YAML parser used to locate compose keys in the source
+use saphyr_parser::{Event, Marker, Parser, ScanError, Span, SpannedEventReceiver};
+use std::collections::HashMap;
+
+use crate::security::{Anchor, Region};
+
+#[derive(Debug, Clone, PartialEq)]
+/// YAML node with the region of the source it was parsed from
+pub struct Node {
+    pub kind: NodeKind,
+    pub region: Region,
+    /// Anchor id (`&name`) of the node, `0` when it has no anchor
+    pub anchor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    Scalar(String),
+    Sequence(Vec<Node>),
+    Mapping(Vec<(Node, Node)>),
+    /// Alias (`*name`) of an anchored node
+    Alias(usize),
+}
+
+impl Node {
+    /// Value of a scalar node
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.kind {
+            NodeKind::Scalar(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed YAML document
+#[derive(Debug, Default)]
+pub struct Tree {
+    pub root: Option<Node>,
+    /// Anchored nodes by anchor id
+    pub anchors: HashMap<usize, Node>,
+}
+
+/// Collection that is still being parsed
+struct Frame {
+    mapping: bool,
+    anchor: usize,
+    children: Vec<Node>,
+    start: Span,
+}
+
+#[derive(Default)]
+struct Builder {
+    stack: Vec<Frame>,
+    tree: Tree,
+}
+
+impl Builder {
+    fn push(&mut self, node: Node) {
+        if node.anchor != 0 {
+            self.tree.anchors.insert(node.anchor, node.clone());
+        }
+        match self.stack.last_mut() {
+            Some(frame) => frame.children.push(node),
+            None if self.tree.root.is_none() => self.tree.root = Some(node),
+            None => {}
+        }
+    }
+
+    fn close(&mut self, span: Span) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        // Block collections have an empty start token and end where the
+        // next token starts, so they end with their last child instead
+        let flow = frame.start.start.index() != frame.start.end.index();
+        let end = match (flow, frame.children.last()) {
+            (false, Some(last)) => last.region,
+            (false, None) => region(frame.start.end, frame.start.end),
+            (true, _) => region(span.end, span.end),
+        };
+        let start = region(frame.start.start, frame.start.start);
+
+        let kind = match frame.mapping {
+            true => {
+                let mut children = frame.children.into_iter();
+                let mut entries = Vec::new();
+                while let (Some(key), Some(value)) = (children.next(), children.next()) {
+                    entries.push((key, value));
+                }
+                NodeKind::Mapping(entries)
+            }
+            false => NodeKind::Sequence(frame.children),
+        };
+        self.push(Node {
+            kind,
+            region: join(start, end),
+            anchor: frame.anchor,
+        });
+    }
+
+    fn open(&mut self, mapping: bool, anchor: usize, span: Span) {
+        self.stack.push(Frame {
+            mapping,
+            anchor,
+            children: Vec::new(),
+            start: span,
+        });
+    }
+}
+
+impl<'input> SpannedEventReceiver<'input> for Builder {
+    fn on_event(&mut self, event: Event<'input>, span: Span) {
+        match event {
+            Event::Scalar(value, _, anchor, _) => self.push(Node {
+                kind: NodeKind::Scalar(value.to_string()),
+                region: region(span.start, span.end),
+                anchor,
+            }),
+            Event::Alias(id) => self.push(Node {
+                kind: NodeKind::Alias(id),
+                region: region(span.start, span.end),
+                anchor: 0,
+            }),
+            Event::SequenceStart(anchor, _) => self.open(false, anchor, span),
+            Event::MappingStart(anchor, _) => self.open(true, anchor, span),
+            Event::SequenceEnd | Event::MappingEnd => self.close(span),
+            _ => {}
+        }
+    }
+}
+
+/// Region between two markers (lines and columns are zero based)
+fn region(start: Marker, end: Marker) -> Region {
+    Region {
+        start_line: start.line() as i32 - 1,
+        start_column: start.col() as i32,
+        end_line: end.line() as i32 - 1,
+        end_column: end.col() as i32,
+    }
+}
+
+/// Empty region at a marker, such as the position of a parse error
+pub fn point(marker: &Marker) -> Region {
+    region(*marker, *marker)
+}
+
+/// Region from the start of `start` to the end of `end`
+pub fn join(start: Region, end: Region) -> Region {
+    Region {
+        start_line: start.start_line,
+        start_column: start.start_column,
+        end_line: end.end_line,
+        end_column: end.end_column,
+    }
+}
+
+/// Parse the first document of a YAML stream
+pub fn parse(data: &str) -> Result<Tree, ScanError> {
+    let mut builder = Builder::default();
+    let mut parser = Parser::new_from_str(data);
+    parser.load(&mut builder, false)?;
+    Ok(builder.tree)
+}
+
+#[derive(Debug, Default)]
+/// Source locations of the keys in a YAML document
+pub struct Mappings {
+    /// Region of every key
+    pub regions: HashMap<String, Region>,
+    /// Anchor that keys pulled in with an alias or merge key came from
+    pub anchors: HashMap<String, Anchor>,
+}
+
+/// Source locations of every key in a YAML document.
+///
+/// Mapping keys are joined with `.` (`services.web.image`) and sequence
+/// items are indexed (`services.web.ports[0]`). List form `KEY=VALUE`
+/// entries are also available by name (`services.web.environment.KEY`).
+///
+/// Aliases and merge keys (`<<: *common`) are expanded, the keys they pull
+/// in point at the anchored source and record the anchor they came from.
+pub fn mappings(data: &str) -> Result<Mappings, ScanError> {
+    let tree = parse(data)?;
+    let mut walker = Walker {
+        anchors: &tree.anchors,
+        defined: HashMap::new(),
+        expanding: Vec::new(),
+        mappings: Mappings::default(),
+    };
+    if let Some(root) = &tree.root {
+        walker.walk(root, "", None);
+    }
+    Ok(walker.mappings)
+}
+
+struct Walker<'a> {
+    anchors: &'a HashMap<usize, Node>,
+    /// Key each anchor is defined at
+    defined: HashMap<usize, String>,
+    /// Anchors being expanded, guards against recursive aliases
+    expanding: Vec<usize>,
+    mappings: Mappings,
+}
+
+impl<'a> Walker<'a> {
+    fn walk(&mut self, node: &'a Node, key: &str, via: Option<&Anchor>) {
+        if node.anchor != 0 && via.is_none() {
+            self.defined.entry(node.anchor).or_insert(key.to_string());
+        }
+
+        match &node.kind {
+            NodeKind::Alias(id) => {
+                let anchor = self.anchor(*id, key, node.region);
+                self.expand(*id, |walker, target| {
+                    walker.walk(target, key, Some(&anchor));
+                });
+            }
+            NodeKind::Mapping(entries) => {
+                let is_merge = |name: &Node| name.as_str() == Some("<<");
+
+                for (name, value) in entries.iter().filter(|(n, _)| !is_merge(n)) {
+                    self.entry(key, name, value, via);
+                }
+
+                // Explicit keys take precedence over merged keys, and earlier
+                // merged mappings take precedence over later ones
+                let mut merged: Vec<&str> = entries
+                    .iter()
+                    .filter_map(|(name, _)| name.as_str())
+                    .collect();
+
+                for (name, value) in entries.iter().filter(|(n, _)| is_merge(n)) {
+                    let aliases: Vec<&Node> = match &value.kind {
+                        NodeKind::Sequence(items) => items.iter().collect(),
+                        _ => vec![value],
+                    };
+
+                    for alias in aliases {
+                        let id = match alias.kind {
+                            NodeKind::Alias(id) => id,
+                            _ => continue,
+                        };
+                        let anchor = self.anchor(id, key, join(name.region, alias.region));
+
+                        self.expand(id, |walker, target| {
+                            let entries = match &target.kind {
+                                NodeKind::Mapping(entries) => entries,
+                                _ => return,
+                            };
+                            for (name, value) in entries {
+                                match name.as_str() {
+                                    Some(n) if !merged.contains(&n) => merged.push(n),
+                                    _ => continue,
+                                }
+                                walker.entry(key, name, value, Some(&anchor));
+                            }
+                        });
+                    }
+                }
+            }
+            NodeKind::Sequence(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let child = format!("{key}[{index}]");
+                    self.insert(&child, item.region, via);
+
+                    if let Some((name, _)) = item.as_str().and_then(|v| v.split_once('=')) {
+                        let child = format!("{key}.{name}");
+                        if !name.is_empty()
+                            && !name.contains(char::is_whitespace)
+                            && !self.mappings.regions.contains_key(&child)
+                        {
+                            self.insert(&child, item.region, via);
+                        }
+                    }
+                    self.walk(item, &child, via);
+                }
+            }
+            NodeKind::Scalar(_) => {}
+        }
+    }
+
+    fn entry(&mut self, key: &str, name: &'a Node, value: &'a Node, via: Option<&Anchor>) {
+        let child = match name.as_str() {
+            Some(name) if key.is_empty() => name.to_string(),
+            Some(name) => format!("{key}.{name}"),
+            None => return,
+        };
+        // Scalars cover the whole `key: value`, collections the key
+        let region = match value.kind {
+            NodeKind::Scalar(_) | NodeKind::Alias(_) => join(name.region, value.region),
+            _ => name.region,
+        };
+        self.insert(&child, region, via);
+        self.walk(value, &child, via);
+    }
+
+    fn insert(&mut self, key: &str, region: Region, via: Option<&Anchor>) {
+        self.mappings.regions.insert(key.to_string(), region);
+        match via {
+            Some(anchor) => self
+                .mappings
+                .anchors
+                .insert(key.to_string(), anchor.clone()),
+            None => self.mappings.anchors.remove(key),
+        };
+    }
+
+    fn anchor(&self, id: usize, target: &str, alias: Region) -> Anchor {
+        Anchor {
+            key: self.defined.get(&id).cloned().unwrap_or_default(),
+            target: target.to_string(),
+            alias,
+        }
+    }
+
+    /// Walk the node of an anchor, unless it is already being expanded
+    fn expand(&mut self, id: usize, walk: impl FnOnce(&mut Self, &'a Node)) {
+        let target = match self.anchors.get(&id) {
+            Some(target) if !self.expanding.contains(&id) => target,
+            _ => return,
+        };
+        self.expanding.push(id);
+        walk(self, target);
+        self.expanding.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: i32, start_column: i32, end_column: i32) -> Region {
+        Region {
+            start_line: line,
+            start_column,
+            end_line: line,
+            end_column,
+        }
+    }
+
+    #[test]
+    fn mappings_indentation() {
+        let data = "services:\n    web:\n        image: nginx   # comment\n\tports: [\"80:80\"]\n";
+        // Tabs are not valid indentation in YAML
+        assert!(mappings(data).is_err());
+
+        let data =
+            "services:\n    web:\n        image: nginx   # comment\n        ports: [\"80:80\"]\n";
+        let mappings = mappings(data).unwrap().regions;
+
+        assert_eq!(mappings.get("services.web"), Some(&at(1, 4, 7)));
+        assert_eq!(mappings.get("services.web.image"), Some(&at(2, 8, 20)));
+        assert_eq!(mappings.get("services.web.ports"), Some(&at(3, 8, 13)));
+        assert_eq!(mappings.get("services.web.ports[0]"), Some(&at(3, 16, 23)));
+    }
+
+    #[test]
+    fn mappings_flow_and_quoted() {
+        let data = "services: {web: {image: nginx, \"privileged\": true}}\n";
+        let mappings = mappings(data).unwrap().regions;
+
+        assert_eq!(mappings.get("services.web.image"), Some(&at(0, 17, 29)));
+        assert_eq!(
+            mappings.get("services.web.privileged"),
+            Some(&at(0, 31, 49))
+        );
+    }
+
+    #[test]
+    fn mappings_multiline() {
+        let data = "services:\n  web:\n    command: |\n      echo 'a: b'\n      - c\n    environment:\n      - DEBUG=1\n      - PASSWORD=secret\n";
+        let mappings = mappings(data).unwrap().regions;
+
+        assert_eq!(mappings.get("services.web.command").unwrap().start_line, 2);
+        assert!(!mappings.contains_key("services.web.command.a"));
+        assert_eq!(
+            mappings.get("services.web.environment.PASSWORD"),
+            Some(&at(7, 8, 23))
+        );
+        assert_eq!(
+            mappings.get("services.web.environment[0]"),
+            Some(&at(6, 8, 15))
+        );
+    }
+
+    #[test]
+    fn mappings_anchors() {
+        let data = "x-common: &common\n  privileged: true\n  image: busybox\nx-env: &env\n  DEBUG: \"1\"\nservices:\n  web:\n    <<: *common\n    image: nginx\n    environment: *env\n";
+        let mappings = mappings(data).unwrap();
+
+        assert_eq!(
+            mappings.regions.get("services.web.privileged"),
+            Some(&at(1, 2, 18))
+        );
+        let anchor = mappings.anchors.get("services.web.privileged").unwrap();
+        assert_eq!(anchor.key, "x-common");
+        assert_eq!(anchor.target, "services.web");
+        assert_eq!(anchor.alias, at(7, 4, 15));
+
+        // Explicit keys take precedence over merged keys
+        assert_eq!(
+            mappings.regions.get("services.web.image"),
+            Some(&at(8, 4, 16))
+        );
+        assert!(!mappings.anchors.contains_key("services.web.image"));
+
+        let anchor = mappings
+            .anchors
+            .get("services.web.environment.DEBUG")
+            .unwrap();
+        assert_eq!(anchor.key, "x-env");
+        assert_eq!(anchor.target, "services.web.environment");
+        assert!(!mappings.anchors.contains_key("x-common.privileged"));
+    }
+}
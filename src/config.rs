@@ -1,8 +1,15 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs::File, path::Path};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+use crate::secrets::Scanner;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 /// Config is where all of the settings for Quibble is stored.
 pub struct Config {
     #[serde(default = "default_registries")]
@@ -14,10 +21,33 @@ pub struct Config {
     pub severity: String,
 
     #[serde(default, rename = "disable-rules")]
+    /// Disable every rule, unless it is enabled in `rules`
     pub disable_rules: bool,
 
     #[serde(default)]
+    /// Settings of rules, by rule name (`docker_socket`) or alert ID
+    /// (`DOCKER_SOCKET`)
     pub rules: HashMap<String, RuleConfig>,
+
+    #[serde(default, rename = "parse-errors")]
+    /// What to do with compose files that fail to parse
+    pub parse_errors: ParseErrorPolicy,
+
+    #[serde(default, rename = "custom-rules")]
+    /// Declarative rules defined by the user
+    pub custom_rules: Vec<CustomRule>,
+
+    #[serde(default, rename = "script-rules")]
+    /// Directory of script rules (`*.rhai`), relative to the config file
+    pub script_rules: Option<PathBuf>,
+
+    #[serde(default, rename = "script-limits")]
+    /// Execution limits of script rules
+    pub script_limits: ScriptLimits,
+
+    #[serde(default)]
+    /// Settings of the secret scanner
+    pub secrets: SecretsConfig,
 }
 
 impl Default for Config {
@@ -27,11 +57,21 @@ impl Default for Config {
             severity: default_severity(),
             disable_rules: false,
             rules: HashMap::new(),
+            parse_errors: ParseErrorPolicy::default(),
+            custom_rules: Vec::new(),
+            script_rules: None,
+            script_limits: ScriptLimits::default(),
+            secrets: SecretsConfig::default(),
         }
     }
 }
 
 impl Config {
+    /// Enable or disable a rule name or alert ID
+    pub fn set_rule_enabled(&mut self, id: &str, enabled: bool) {
+        self.rules.entry(id.to_string()).or_default().enabled = Some(enabled);
+    }
+
     pub fn load(path: &Path) -> Result<Self> {
         if let Some(ext) = path.extension() {
             let file = match File::open(path) {
@@ -42,29 +82,160 @@ impl Config {
                 }
             };
 
-            match ext.to_str() {
-                Some("yml") | Some("yaml") => {
-                    return Ok(serde_yaml::from_reader(file)?);
-                }
-                Some("json") => {
-                    return Ok(serde_json::from_reader(file)?);
-                }
+            let mut config: Config = match ext.to_str() {
+                Some("yml") | Some("yaml") => serde_yaml::from_reader(file)?,
+                Some("json") => serde_json::from_reader(file)?,
                 Some("toml") => {
                     return Err(anyhow!("Toml file is currently not supported"));
                 }
                 _ => {
                     return Err(anyhow!("Unknown extension"));
                 }
+            };
+
+            if let (Some(scripts), Some(parent)) = (&config.script_rules, path.parent()) {
+                config.script_rules = Some(parent.join(scripts));
             }
+            Scanner::new(&config.secrets).context("Invalid `secrets` allowlist")?;
+            return Ok(config);
         }
         Ok(Config::default())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Policy for compose files that fail to parse
+pub enum ParseErrorPolicy {
+    /// Report the errors and fail the run
+    #[default]
+    Fail,
+    /// Report the errors without failing the run
+    Warn,
+    /// Do not report the errors
+    Ignore,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Settings of a rule or alert ID
 pub struct RuleConfig {
+    /// Enable or disable the rule
     pub enabled: Option<bool>,
+    /// Severity of the alerts, replacing the rule default
+    pub severity: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// Declarative rule that reports an alert for every value at `path` that
+/// matches the predicate
+///
+/// ```yaml
+/// custom-rules:
+///   - id: LOGGING_DRIVER
+///     message: Service `{service}` does not set a logging driver
+///     path: services.*.logging.driver
+///     exists: false
+/// ```
+pub struct CustomRule {
+    /// Alert ID
+    pub id: String,
+    /// Short name of the rule, defaults to the ID
+    pub title: Option<String>,
+    /// Alert details, `{service}`, `{key}` and `{value}` are replaced
+    pub message: Option<String>,
+    /// Severity of the alerts (default: medium)
     pub severity: Option<String>,
+    /// What the rule checks and why
+    pub description: Option<String>,
+    /// How to fix the problem
+    pub remediation: Option<String>,
+    /// CWE references (`798` or `CWE-798`)
+    #[serde(default)]
+    pub cwe: Vec<String>,
+    /// OWASP Docker Top 10 categories (`D01` to `D10`)
+    #[serde(default)]
+    pub owasp: Vec<String>,
+    /// Compose key path, `*` matches every key or item
+    /// (`services.*.labels["com.company.owner"]`)
+    pub path: String,
+    #[serde(flatten)]
+    pub predicate: Predicate,
+    /// Report values that do not match the predicate instead
+    #[serde(default)]
+    pub not: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// Conditions a value matches, every condition that is set has to match
+pub struct Predicate {
+    /// Value is set (or not set)
+    pub exists: Option<bool>,
+    /// Value is equal to
+    pub equals: Option<serde_yaml::Value>,
+    /// Value matches the regular expression
+    pub regex: Option<String>,
+    /// Value is one of
+    #[serde(rename = "in")]
+    pub in_list: Option<Vec<serde_yaml::Value>>,
+    /// Numeric value is greater than
+    pub greater_than: Option<f64>,
+    /// Numeric value is less than
+    pub less_than: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+/// Execution limits of a script rule, per compose file
+pub struct ScriptLimits {
+    /// Statements and expressions that can be evaluated
+    pub max_operations: u64,
+    /// Depth of nested function calls
+    pub max_call_depth: usize,
+    /// Length of strings, arrays and maps
+    pub max_size: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        ScriptLimits {
+            max_operations: 1_000_000,
+            max_call_depth: 32,
+            max_size: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+/// Settings of the secret scanner
+///
+/// ```yaml
+/// secrets:
+///   allowlist:
+///     - ^sk_test_
+///   allow-names:
+///     - ^DEMO_
+/// ```
+pub struct SecretsConfig {
+    /// Regular expressions of values that are not secrets
+    pub allowlist: Vec<String>,
+    /// Regular expressions of variable names that are not checked
+    pub allow_names: Vec<String>,
+    /// Entropy (bits per character) of random looking values that are
+    /// reported as secrets
+    pub entropy: f64,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        SecretsConfig {
+            allowlist: Vec::new(),
+            allow_names: Vec::new(),
+            entropy: 4.0,
+        }
+    }
 }
 
 // Default severity
@@ -17,6 +17,7 @@ pub struct ContainerImage {
     /// Digest
     pub digest: Option<String>,
     /// Signature
+    #[allow(dead_code)]
     pub signature: Option<String>,
 }
 
@@ -45,6 +46,11 @@ impl ContainerImage {
             return Err(anyhow!("Unsupported container splitting:"));
         }
 
+        if let Some((start, end)) = result.name.split_once('@') {
+            result.digest = Some(end.to_string());
+            result.name = start.to_string();
+        }
+
         if let Some((start, end)) = result.name.split_once(':') {
             result.tag = end.to_string();
             result.name = start.to_string();
@@ -73,7 +79,11 @@ impl Display for ContainerImage {
             f,
             "{}/{}/{}:{}",
             self.instance, self.namespace, self.name, self.tag
-        )
+        )?;
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+        Ok(())
     }
 }
 
@@ -113,6 +123,16 @@ mod tests {
         assert_eq!(image.tag, String::from("1.20"));
     }
 
+    #[test]
+    fn parse_digest() {
+        let container = String::from("gitea/gitea:1.20@sha256:abcdef");
+        let image = ContainerImage::parse(container).unwrap();
+
+        assert_eq!(image.name, String::from("gitea"));
+        assert_eq!(image.tag, String::from("1.20"));
+        assert_eq!(image.digest, Some(String::from("sha256:abcdef")));
+    }
+
     #[test]
     fn parse_instance() {
         let container = String::from("ghcr.io/gitea/gitea");
@@ -1,9 +1,16 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use crate::security::Alert;
+use crate::{
+    baseline::BaselineFinding,
+    rules::RuleMetadata,
+    security::{self, Alert, BaselineState, Diagnostic, RuleID},
+};
+
+/// Documentation of the rules
+const HELP_URI: &str = "https://github.com/GeekMasher/quibble/blob/main/docs/rules.md";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SarifFile {
@@ -23,6 +30,7 @@ impl Default for SarifFile {
 
 impl SarifFile {
     /// Create a new SarifFile
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> SarifBuilder {
         SarifBuilder::new()
     }
@@ -39,6 +47,9 @@ pub struct SarifBuilder {
     tool: Tool,
     base: PathBuf,
     alerts: Vec<Alert>,
+    diagnostics: Vec<Diagnostic>,
+    rules: Vec<RuleMetadata>,
+    fixed: Vec<BaselineFinding>,
 }
 
 impl SarifBuilder {
@@ -53,6 +64,27 @@ impl SarifBuilder {
         self
     }
 
+    /// Add problems that stopped files from being checked, reported as
+    /// tool execution notifications
+    pub fn add_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) -> &mut Self {
+        self.diagnostics.extend(diagnostics);
+        self
+    }
+
+    /// Add the metadata of the rules that were run, used for the rule
+    /// descriptors
+    pub fn add_rules(&mut self, rules: Vec<RuleMetadata>) -> &mut Self {
+        self.rules.extend(rules);
+        self
+    }
+
+    /// Add the baseline findings that are fixed, reported as `absent`
+    /// results
+    pub fn add_fixed(&mut self, fixed: Vec<BaselineFinding>) -> &mut Self {
+        self.fixed.extend(fixed);
+        self
+    }
+
     pub fn base(&mut self, base: &PathBuf) -> &mut Self {
         self.base = std::fs::canonicalize(base).unwrap_or(base.clone());
         self
@@ -72,16 +104,26 @@ impl SarifBuilder {
 
     pub fn build(&mut self) -> Result<SarifFile> {
         let mut sarif = SarifFile::default();
-        let mut run = Run::default();
-        run.tool = self.tool.clone();
+        let mut run = Run {
+            tool: self.tool.clone(),
+            ..Default::default()
+        };
+
+        run.taxonomies = self.taxonomies();
+        for metadata in &self.rules {
+            let mut rule = Rule::from(metadata);
+            rule.relationships = metadata
+                .references
+                .iter()
+                .filter_map(|reference| self.relationship(&run.taxonomies, reference))
+                .collect();
+            run.tool.driver.rules.push(rule);
+        }
 
         for alert in &self.alerts {
-            let path = alert
-                .path
-                .path
-                .strip_prefix(&self.base)
-                .unwrap_or(&alert.path.path);
+            let uri = self.relative(&alert.path.path);
 
+            // Alerts of rules without metadata
             if !run
                 .tool
                 .driver
@@ -98,7 +140,7 @@ impl SarifBuilder {
                     full_description: Message {
                         text: alert.details.to_string(),
                     },
-                    help_uri: String::from("https://github.com/GeekMasher/quibble"),
+                    help_uri: String::from(HELP_URI),
                     properties: Properties {
                         id: alert.id.to_string(),
                         severity: alert.severity.to_string().to_lowercase(),
@@ -106,6 +148,7 @@ impl SarifBuilder {
                         precision: String::from("high"),
                         ..Default::default()
                     },
+                    ..Default::default()
                 });
             }
 
@@ -117,28 +160,246 @@ impl SarifBuilder {
                 locations: vec![Location {
                     physical_location: PhysicalLocation {
                         artifact_location: ArtifactLocation {
-                            uri: path.display().to_string(),
+                            uri: uri.clone(),
                             ..Default::default()
                         },
-                        region: Region {
-                            start_line: alert.path.line.unwrap_or(0) + 1,
+                        region: Region::from(alert.path.region),
+                    },
+                    ..Default::default()
+                }],
+                related_locations: alert
+                    .path
+                    .anchor
+                    .iter()
+                    .map(|anchor| Location {
+                        id: Some(1),
+                        message: Some(Message {
+                            text: format!("Anchor `{}` used by `{}`", anchor.key, anchor.target),
+                        }),
+                        physical_location: PhysicalLocation {
+                            artifact_location: ArtifactLocation {
+                                uri: uri.clone(),
+                                ..Default::default()
+                            },
+                            region: Region::from(Some(anchor.alias)),
+                        },
+                    })
+                    .collect(),
+                baseline_state: alert.baseline.map(|state| match state {
+                    BaselineState::New => String::from("new"),
+                    BaselineState::Unchanged => String::from("unchanged"),
+                }),
+                suppressions: alert
+                    .suppression
+                    .iter()
+                    .map(|suppression| Suppression {
+                        kind: String::from("inSource"),
+                        justification: suppression.justification.clone(),
+                    })
+                    .collect(),
+                fixes: alert
+                    .fix
+                    .iter()
+                    .map(|fix| Fix {
+                        description: Message {
+                            text: fix.description.clone(),
+                        },
+                        artifact_changes: fix
+                            .edits
+                            .iter()
+                            .map(|edit| ArtifactChange {
+                                artifact_location: ArtifactLocation {
+                                    uri: self.relative(&edit.path),
+                                    ..Default::default()
+                                },
+                                replacements: vec![Replacement {
+                                    deleted_region: Region::from(Some(edit.region)),
+                                    inserted_content: ArtifactContent {
+                                        text: edit.text.clone(),
+                                    },
+                                }],
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+                properties: ResultProperties {
+                    profiles: alert.path.profiles.clone(),
+                    service: alert.service.clone(),
+                    key: alert.path.key.clone(),
+                    value: alert.value.clone(),
+                },
+            });
+        }
+
+        for finding in &self.fixed {
+            run.results.push(RunResult {
+                rule_id: finding.id.clone(),
+                message: Message {
+                    text: finding.details.clone(),
+                },
+                locations: vec![Location {
+                    physical_location: PhysicalLocation {
+                        artifact_location: ArtifactLocation {
+                            uri: finding.path.display().to_string(),
                             ..Default::default()
                         },
+                        region: Region::from(None),
                     },
+                    ..Default::default()
                 }],
+                baseline_state: Some(String::from("absent")),
+                ..Default::default()
             });
         }
 
+        run.invocations.push(Invocation {
+            execution_successful: self.diagnostics.is_empty(),
+            tool_execution_notifications: self
+                .diagnostics
+                .iter()
+                .map(|diagnostic| Notification {
+                    level: String::from("error"),
+                    associated_rule: diagnostic
+                        .rule
+                        .clone()
+                        .map(|id| ReportingDescriptorReference { id }),
+                    message: Message {
+                        text: diagnostic.message.to_string(),
+                    },
+                    locations: vec![Location {
+                        physical_location: PhysicalLocation {
+                            artifact_location: ArtifactLocation {
+                                uri: self.relative(&diagnostic.path.path),
+                                ..Default::default()
+                            },
+                            region: Region::from(diagnostic.path.region),
+                        },
+                        ..Default::default()
+                    }],
+                })
+                .collect(),
+        });
+
         sarif.runs.push(run);
         Ok(sarif)
     }
+
+    /// CWE and OWASP Docker Top 10 taxonomies with the taxa the rules
+    /// reference
+    fn taxonomies(&self) -> Vec<Taxonomy> {
+        let mut cwes: Vec<&String> = Vec::new();
+        let mut categories: Vec<&String> = Vec::new();
+        for reference in self.rules.iter().flat_map(|m| &m.references) {
+            match reference {
+                RuleID::Cwe(id) if !cwes.contains(&id) => cwes.push(id),
+                RuleID::Owasp(id) if !categories.contains(&id) => categories.push(id),
+                _ => {}
+            }
+        }
+        cwes.sort_by_key(|id| id.parse::<u32>().unwrap_or(u32::MAX));
+        categories.sort();
+
+        let mut taxonomies = Vec::new();
+        if !cwes.is_empty() {
+            taxonomies.push(Taxonomy {
+                name: String::from("CWE"),
+                organization: String::from("MITRE"),
+                information_uri: String::from("https://cwe.mitre.org/"),
+                taxa: cwes
+                    .into_iter()
+                    .map(|id| Taxon {
+                        id: id.clone(),
+                        name: Some(format!("CWE-{id}")),
+                        help_uri: Some(format!("https://cwe.mitre.org/data/definitions/{id}.html")),
+                    })
+                    .collect(),
+            });
+        }
+        if !categories.is_empty() {
+            taxonomies.push(Taxonomy {
+                name: String::from("OWASP Docker Top 10"),
+                organization: String::from("OWASP"),
+                information_uri: String::from("https://owasp.org/www-project-docker-top-10/"),
+                taxa: categories
+                    .into_iter()
+                    .map(|id| Taxon {
+                        id: id.clone(),
+                        name: RuleID::Owasp(id.clone()).title().map(str::to_string),
+                        ..Default::default()
+                    })
+                    .collect(),
+            });
+        }
+        taxonomies
+    }
+
+    /// Relationship of a rule to the taxon of a reference
+    fn relationship(&self, taxonomies: &[Taxonomy], reference: &RuleID) -> Option<Relationship> {
+        let (name, id) = match reference {
+            RuleID::Cwe(id) => ("CWE", id),
+            RuleID::Owasp(id) => ("OWASP Docker Top 10", id),
+            RuleID::Quibble(_) => return None,
+        };
+        let component = taxonomies.iter().position(|t| t.name == name)?;
+        let index = taxonomies[component]
+            .taxa
+            .iter()
+            .position(|t| &t.id == id)?;
+
+        Some(Relationship {
+            target: RelationshipTarget {
+                id: id.clone(),
+                index,
+                tool_component: ToolComponentReference {
+                    name: name.to_string(),
+                    index: component,
+                },
+            },
+            kinds: vec![String::from("superset")],
+        })
+    }
+
+    /// Path relative to the base source
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.base)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Run {
     pub tool: Tool,
+    /// CWE and OWASP Docker Top 10 taxonomies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub taxonomies: Vec<Taxonomy>,
     pub results: Vec<RunResult>,
     pub artifacts: Vec<Artifact>,
+    pub invocations: Vec<Invocation>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Invocation {
+    #[serde(rename = "executionSuccessful")]
+    pub execution_successful: bool,
+    #[serde(rename = "toolExecutionNotifications")]
+    pub tool_execution_notifications: Vec<Notification>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Notification {
+    pub level: String,
+    /// Rule that failed
+    #[serde(rename = "associatedRule", skip_serializing_if = "Option::is_none")]
+    pub associated_rule: Option<ReportingDescriptorReference>,
+    pub message: Message,
+    pub locations: Vec<Location>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReportingDescriptorReference {
+    pub id: String,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -147,10 +408,86 @@ pub struct RunResult {
     pub rule_id: String,
     pub message: Message,
     pub locations: Vec<Location>,
+    #[serde(rename = "relatedLocations", skip_serializing_if = "Vec::is_empty")]
+    pub related_locations: Vec<Location>,
+    #[serde(rename = "baselineState", skip_serializing_if = "Option::is_none")]
+    pub baseline_state: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suppressions: Vec<Suppression>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixes: Vec<Fix>,
+    #[serde(default, skip_serializing_if = "ResultProperties::is_empty")]
+    pub properties: ResultProperties,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Suppression {
+    /// `inSource` for `quibble-ignore` comments
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+/// Suggested fix, applied with `--fix`
+pub struct Fix {
+    pub description: Message,
+    #[serde(rename = "artifactChanges")]
+    pub artifact_changes: Vec<ArtifactChange>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ArtifactChange {
+    #[serde(rename = "artifactLocation")]
+    pub artifact_location: ArtifactLocation,
+    pub replacements: Vec<Replacement>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+/// Replace a region of a file, an empty region inserts the content
+pub struct Replacement {
+    #[serde(rename = "deletedRegion")]
+    pub deleted_region: Region,
+    #[serde(rename = "insertedContent")]
+    pub inserted_content: ArtifactContent,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ArtifactContent {
+    pub text: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ResultProperties {
+    /// Compose profiles of the service the result belongs to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+    /// Service the result belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Compose key of the result (`services.web.cap_add[1]`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Value that triggered the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl ResultProperties {
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+            && self.service.is_none()
+            && self.key.is_none()
+            && self.value.is_none()
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<Message>,
     #[serde(rename = "physicalLocation")]
     pub physical_location: PhysicalLocation,
 }
@@ -192,6 +529,23 @@ pub struct Region {
     pub end_column: Option<i32>,
 }
 
+impl From<Option<security::Region>> for Region {
+    fn from(region: Option<security::Region>) -> Self {
+        match region {
+            Some(r) => Region {
+                start_line: r.start_line + 1,
+                start_column: Some(r.start_column + 1),
+                end_line: Some(r.end_line + 1),
+                end_column: Some(r.end_column + 1),
+            },
+            None => Region {
+                start_line: 1,
+                ..Default::default()
+            },
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Tool {
     pub driver: Driver,
@@ -214,9 +568,99 @@ pub struct Rule {
     pub short_description: Message,
     #[serde(rename = "fullDescription")]
     pub full_description: Message,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<Help>,
     #[serde(rename = "helpUri")]
     pub help_uri: String,
     pub properties: Properties,
+    /// CWE and OWASP Docker Top 10 taxa of the rule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relationships: Vec<Relationship>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Taxonomy {
+    pub name: String,
+    pub organization: String,
+    #[serde(rename = "informationUri")]
+    pub information_uri: String,
+    pub taxa: Vec<Taxon>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Taxon {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "helpUri", skip_serializing_if = "Option::is_none")]
+    pub help_uri: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Relationship {
+    pub target: RelationshipTarget,
+    /// `superset`, the taxon covers more than the rule
+    pub kinds: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RelationshipTarget {
+    pub id: String,
+    /// Index of the taxon in the taxonomy
+    pub index: usize,
+    #[serde(rename = "toolComponent")]
+    pub tool_component: ToolComponentReference,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ToolComponentReference {
+    pub name: String,
+    /// Index of the taxonomy in the run
+    pub index: usize,
+}
+
+impl From<&RuleMetadata> for Rule {
+    fn from(metadata: &RuleMetadata) -> Self {
+        let mut tags = metadata.tags.clone();
+        tags.extend(metadata.references.iter().map(|r| r.to_string()));
+        // Used by GitHub code scanning to show the CWE of an alert
+        tags.extend(metadata.references.iter().filter_map(|r| match r {
+            RuleID::Cwe(id) => Some(format!("external/cwe/cwe-{id}")),
+            _ => None,
+        }));
+        tags.extend(metadata.cis.iter().map(|c| format!("CIS-{c}")));
+
+        Rule {
+            id: metadata.id.to_string(),
+            name: metadata.title.clone(),
+            short_description: Message {
+                text: metadata.title.clone(),
+            },
+            full_description: Message {
+                text: metadata.description.clone(),
+            },
+            help: Some(Help {
+                text: metadata.remediation.clone(),
+                markdown: metadata.markdown(),
+            }),
+            help_uri: String::from(HELP_URI),
+            properties: Properties {
+                id: metadata.id.to_string(),
+                tags,
+                severity: metadata.severity.to_string().to_lowercase(),
+                security_severity: metadata.severity.cvss(),
+                precision: String::from("high"),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Help {
+    pub text: String,
+    pub markdown: String,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -239,3 +683,58 @@ pub struct Properties {
 pub struct Artifact {
     pub location: ArtifactLocation,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::Severity;
+
+    #[test]
+    fn taxonomies() {
+        let sarif = SarifFile::new()
+            .add_rules(vec![
+                RuleMetadata::new(RuleID::Quibble(String::from("HOST_NETWORK")), "Host")
+                    .severity(Severity::Medium)
+                    .cwe("668")
+                    .owasp("D03")
+                    .owasp("D04"),
+                RuleMetadata::new(RuleID::Quibble(String::from("PRIVILEGED")), "Privileged")
+                    .cwe("250")
+                    .owasp("D04"),
+            ])
+            .build()
+            .unwrap();
+        let run = &sarif.runs[0];
+
+        let names: Vec<&str> = run.taxonomies.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["CWE", "OWASP Docker Top 10"]);
+        let cwes: Vec<&str> = run.taxonomies[0]
+            .taxa
+            .iter()
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(cwes, vec!["250", "668"]);
+        assert_eq!(
+            run.taxonomies[1].taxa[1].name.as_deref(),
+            Some("Secure Defaults and Hardening")
+        );
+
+        let rule = &run.tool.driver.rules[0];
+        let targets: Vec<(&str, usize, usize)> = rule
+            .relationships
+            .iter()
+            .map(|r| {
+                (
+                    r.target.id.as_str(),
+                    r.target.index,
+                    r.target.tool_component.index,
+                )
+            })
+            .collect();
+        assert_eq!(targets, vec![("668", 1, 0), ("D03", 0, 1), ("D04", 1, 1)]);
+        assert!(rule
+            .properties
+            .tags
+            .contains(&String::from("external/cwe/cwe-668")));
+    }
+}
@@ -1,7 +1,8 @@
 use std::{
+    collections::BTreeMap,
     fs::canonicalize,
     path::{Path, PathBuf},
-    process,
+    process::ExitCode,
 };
 
 use anyhow::Result;
@@ -9,37 +10,107 @@ use clap::Parser;
 use console::style;
 use log::{debug, error, info};
 
+mod baseline;
+mod benchmark;
 mod cli;
 mod compose;
 mod config;
 mod containers;
+mod fix;
 mod formatters;
 mod rules;
+mod secrets;
 mod security;
 
 use crate::{
-    cli::{ArgumentCommands, Arguments, AUTHOR, BANNER, VERSION_NUMBER},
-    config::Config,
+    baseline::{Baseline, BaselineFinding},
+    benchmark::{Report, Status},
+    cli::{ArgumentCommands, Arguments, ExitStatus, AUTHOR, BANNER, VERSION_NUMBER},
+    compose::{discovery::DiscoveryOptions, ComposeFile, LoadError, LoadOptions},
+    config::{Config, ParseErrorPolicy},
+    fix::Fixer,
     formatters::sarif::SarifFile,
-    rules::Rules,
-    security::{Alert, Severity},
+    rules::{RuleMetadata, Rules},
+    security::{Alert, BaselineState, Diagnostic, RuleID, Severity},
 };
 
-fn output_cli(_config: &Config, severity: Severity, results: Vec<Alert>) -> Result<bool> {
-    // If a single alert
-    let mut alert_present: bool = false;
+/// Alert is reported (not suppressed, not in the baseline and at or above
+/// the severity threshold)
+fn reportable(severity: Severity, alert: &Alert) -> bool {
+    alert.suppression.is_none()
+        && alert.baseline != Some(BaselineState::Unchanged)
+        && alert.severity <= severity
+}
 
-    let mut previous = PathBuf::new();
+fn output_cli(
+    _config: &Config,
+    metadata: &[RuleMetadata],
+    severity: Severity,
+    mut results: Vec<Alert>,
+    diagnostics: &[Diagnostic],
+    fixed: &[BaselineFinding],
+) -> Result<()> {
+    if !diagnostics.is_empty() {
+        println!("\n{:^32}\n", style("Errors").bold().red());
+    }
+    for diagnostic in diagnostics {
+        println!(
+            "[{:^22}] {}: {}",
+            style("Error").red().to_string(),
+            diagnostic.path,
+            diagnostic.message
+        );
+    }
+
+    // Group by file and service, keeping the severity order in each group
+    results.sort_by(|a, b| (&a.path.path, &a.service).cmp(&(&b.path.path, &b.service)));
+
+    let mut previous: Option<(PathBuf, Option<String>)> = None;
+    let mut suppressed = 0;
+    let mut baselined = 0;
+    let mut categories: BTreeMap<RuleID, usize> = BTreeMap::new();
+    let mut uncategorised = 0;
 
     for result in results {
+        if result.suppression.is_some() {
+            debug!("Suppressed: {}", result);
+            suppressed += 1;
+            continue;
+        }
+        if result.baseline == Some(BaselineState::Unchanged) {
+            debug!("In baseline: {}", result);
+            baselined += 1;
+            continue;
+        }
         if severity < result.severity {
             debug!("Skipping: {}", result);
             continue;
         }
 
-        if previous != result.path.path.clone() {
-            println!("\n{:^32}\n", style(&result.path).bold().blue());
-            previous = result.path.path.clone();
+        let mut reported = metadata
+            .iter()
+            .filter(|m| m.id == result.id)
+            .flat_map(|m| m.categories())
+            .peekable();
+        if reported.peek().is_none() {
+            uncategorised += 1;
+        }
+        for category in reported {
+            *categories.entry(category.clone()).or_default() += 1;
+        }
+
+        let group = (result.path.path.clone(), result.service.clone());
+        if previous.as_ref() != Some(&group) {
+            if previous.as_ref().map(|(path, _)| path) != Some(&group.0) {
+                println!(
+                    "\n{:^32}\n",
+                    style(result.path.path.display()).bold().blue()
+                );
+            }
+            if let Some(service) = &group.1 {
+                println!("{} {}", style("service").dim(), style(service).bold());
+            }
+            previous = Some(group);
         }
 
         let severity = match result.severity {
@@ -52,14 +123,174 @@ fn output_cli(_config: &Config, severity: Severity, results: Vec<Alert>) -> Resu
             _ => style(&result.severity).green(),
         }
         .to_string();
-        println!("[{:^22}] {}", severity, &result.details);
+        match result.path.region {
+            Some(r) => println!(
+                "[{:^22}] {} {}",
+                severity,
+                &result.details,
+                style(format!("({}:{})", r.start_line + 1, r.start_column + 1)).dim()
+            ),
+            None => println!("[{:^22}] {}", severity, &result.details),
+        }
+        if !result.path.profiles.is_empty() {
+            println!(
+                "{:>24} {}",
+                "",
+                style(format!("profiles: {}", result.path.profiles.join(", "))).dim()
+            );
+        }
+        if let Some(anchor) = &result.path.anchor {
+            println!(
+                "{:>24} {}",
+                "",
+                style(format!(
+                    "from anchor `{}` used by `{}` ({}:{})",
+                    anchor.key,
+                    anchor.target,
+                    anchor.alias.start_line + 1,
+                    anchor.alias.start_column + 1
+                ))
+                .dim()
+            );
+        }
+        if let Some(fix) = &result.fix {
+            println!(
+                "{:>24} {}",
+                "",
+                style(format!("fix: {}", fix.description)).dim()
+            );
+        }
+    }
+
+    // Findings by OWASP Docker Top 10 category, a finding can be in more
+    // than one category
+    if !categories.is_empty() {
+        println!("\n{:^32}\n", style("OWASP Docker Top 10").bold().blue());
+    }
+    for (category, count) in &categories {
+        println!(
+            "[{:^22}] {} {}",
+            style(count).yellow().to_string(),
+            style(category).bold(),
+            category.title().unwrap_or_default()
+        );
+    }
+    if !categories.is_empty() && uncategorised > 0 {
+        println!(
+            "[{:^22}] {}",
+            style(uncategorised).yellow().to_string(),
+            style("Uncategorised").dim()
+        );
+    }
+
+    if !fixed.is_empty() {
+        println!("\n{:^32}\n", style("Fixed").bold().green());
+    }
+    for finding in fixed {
+        let location = match &finding.key {
+            Some(key) => format!("{} ({key})", finding.path.display()),
+            None => finding.path.display().to_string(),
+        };
+        println!(
+            "[{:^22}] {}: {}",
+            style("Fixed").green().to_string(),
+            finding.id,
+            style(location).dim()
+        );
+    }
 
-        alert_present = true;
+    if baselined > 0 {
+        println!(
+            "\n{}",
+            style(format!("{baselined} alert(s) already in the baseline")).dim()
+        );
+    }
+    if suppressed > 0 {
+        println!(
+            "\n{}",
+            style(format!(
+                "{suppressed} alert(s) suppressed by `quibble-ignore` comments"
+            ))
+            .dim()
+        );
+    }
+    Ok(())
+}
+
+fn output_benchmark(report: &Report, diagnostics: &[Diagnostic]) {
+    if !diagnostics.is_empty() {
+        println!("\n{:^32}\n", style("Errors").bold().red());
+    }
+    for diagnostic in diagnostics {
+        println!(
+            "[{:^22}] {}: {}",
+            style("Error").red().to_string(),
+            diagnostic.path,
+            diagnostic.message
+        );
+    }
+
+    let mut previous: Option<&Vec<String>> = None;
+    for service in &report.services {
+        if previous != Some(&service.files) {
+            println!("\n{:^32}\n", style(service.files.join(", ")).bold().blue());
+            previous = Some(&service.files);
+        }
+        println!(
+            "{} {} {}",
+            style("service").dim(),
+            style(&service.service).bold(),
+            style(format!("({}%)", service.score)).dim()
+        );
+
+        for control in &service.controls {
+            let status = match control.status {
+                Status::Pass => style("Pass").green(),
+                Status::Fail => style("Fail").red(),
+                Status::NotApplicable => style("N/A").dim(),
+                Status::NotEvaluated => style("Error").red(),
+            }
+            .to_string();
+            println!("[{:^22}] {:<5} {}", status, control.id, control.title);
+            for alert in &control.alerts {
+                println!(
+                    "{:>30} {}",
+                    "",
+                    style(format!("{}: {}", alert.id, alert.details)).dim()
+                );
+            }
+        }
     }
-    Ok(alert_present)
+
+    println!(
+        "\n{}: {}% ({} passed, {} failed, {} not applicable, {} not evaluated)",
+        style(&report.benchmark).bold(),
+        report.score,
+        report.passed,
+        report.failed,
+        report.not_applicable,
+        report.not_evaluated
+    );
 }
 
-fn main() -> Result<()> {
+fn output_rules(rules: &Rules) {
+    for (name, metadata) in rules.rule_metadata() {
+        let disabled = match rules.is_enabled(name, &metadata.id.to_string()) {
+            true => String::new(),
+            false => style(" (disabled)").dim().to_string(),
+        };
+        println!(
+            "[{:^22}] {} {}{}",
+            style(&metadata.severity).yellow().to_string(),
+            style(&metadata.id).bold(),
+            metadata.title,
+            disabled
+        );
+        println!("{:>24} {}", "", style(&metadata.description).dim());
+    }
+}
+
+fn main() -> ExitCode {
     let arguments = Arguments::parse();
 
     let log_level = match arguments.debug {
@@ -81,7 +312,23 @@ fn main() -> Result<()> {
         );
     }
 
-    let config = Config::load(Path::new(&arguments.config))?;
+    match run(&arguments) {
+        Ok(status) => status.into(),
+        Err(err) => {
+            error!("{err:?}");
+            ExitStatus::ScanError.into()
+        }
+    }
+}
+
+fn run(arguments: &Arguments) -> Result<ExitStatus> {
+    let config = match Config::load(Path::new(&arguments.config)) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Invalid config {}: {err:#}", arguments.config.display());
+            return Ok(ExitStatus::Usage);
+        }
+    };
     debug!("Config loaded: {:?}", config);
 
     debug!("Finished initialising, starting main workflow...");
@@ -90,14 +337,112 @@ fn main() -> Result<()> {
     match &arguments.commands {
         ArgumentCommands::Compose {
             path,
+            files,
+            env_files,
+            process_env,
+            profiles,
+            all_profiles,
+            exclude,
+            max_depth,
+            follow_symlinks,
             output,
             format,
             filter,
             base,
+            enable_rules,
+            disable_rules,
+            baseline,
+            write_baseline,
+            fix,
+            dry_run,
+            benchmark,
+            parse_errors,
             disable_fail,
         } => {
-            let full_path = canonicalize(path)?;
-            let compose_files = compose::find(&full_path)?;
+            match (format.as_str(), output, benchmark) {
+                ("cli", _, _) | ("sarif", Some(_), None) | ("json", Some(_), Some(_)) => {}
+                ("sarif" | "json", None, _) => {
+                    error!("No output file specified...");
+                    return Ok(ExitStatus::Usage);
+                }
+                ("sarif", Some(_), Some(_)) => {
+                    error!("SARIF output is not supported with `--benchmark`");
+                    return Ok(ExitStatus::Usage);
+                }
+                ("json", Some(_), None) => {
+                    error!("JSON output is only supported with `--benchmark`");
+                    return Ok(ExitStatus::Usage);
+                }
+                _ => {
+                    error!("Unknown format output: `{format}`");
+                    return Ok(ExitStatus::Usage);
+                }
+            }
+
+            // `COMPOSE_PROFILES` is only used with the process environment
+            let profiles = match std::env::var("COMPOSE_PROFILES") {
+                Ok(env) if profiles.is_empty() && *process_env => env
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect(),
+                _ => profiles.clone(),
+            };
+
+            let options = LoadOptions {
+                env_files: env_files.clone(),
+                process_env: *process_env,
+                profiles,
+                all_profiles: *all_profiles,
+            };
+            let discovery = DiscoveryOptions {
+                exclude: exclude.clone(),
+                max_depth: *max_depth,
+                follow_symlinks: *follow_symlinks,
+            };
+
+            let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+            let compose_files = match files.is_empty() {
+                true => {
+                    let path = match canonicalize(path) {
+                        Ok(path) => path,
+                        Err(err) => {
+                            error!("Invalid path {}: {err}", path.display());
+                            return Ok(ExitStatus::Usage);
+                        }
+                    };
+                    compose::find(&path, &options, &discovery, &mut diagnostics)?
+                }
+                false => {
+                    let files = match files
+                        .iter()
+                        .map(canonicalize)
+                        .collect::<Result<Vec<PathBuf>, _>>()
+                    {
+                        Ok(files) => files,
+                        Err(err) => {
+                            error!("Invalid compose file: {err}");
+                            return Ok(ExitStatus::Usage);
+                        }
+                    };
+                    match ComposeFile::load(&files, &options) {
+                        Ok(c) => vec![c],
+                        Err(err) => {
+                            diagnostics.push(LoadError::diagnostic(&files, &err));
+                            Vec::new()
+                        }
+                    }
+                }
+            };
+
+            let parse_errors = parse_errors.unwrap_or(config.parse_errors);
+            if parse_errors == ParseErrorPolicy::Ignore {
+                for diagnostic in diagnostics.drain(..) {
+                    debug!("Ignoring parse error: {diagnostic}");
+                }
+            }
+            let parse_failed = parse_errors == ParseErrorPolicy::Fail && !diagnostics.is_empty();
 
             let mut results: Vec<Alert> = Vec::new();
 
@@ -108,48 +453,176 @@ fn main() -> Result<()> {
             };
             debug!("Severity set :: {severity}");
 
+            let mut config = config.clone();
+            for id in enable_rules {
+                config.set_rule_enabled(id, true);
+            }
+            for id in disable_rules {
+                config.set_rule_enabled(id, false);
+            }
+
             let mut rules = Rules::new(config.clone());
+            if benchmark.is_some() {
+                rules.enable_benchmark();
+            }
             debug!("Rule count: {}", rules.len());
+            if !rules.invalid().is_empty() {
+                for diagnostic in rules.invalid() {
+                    error!("{}", diagnostic.message);
+                }
+                return Ok(ExitStatus::Usage);
+            }
+
+            let base = canonicalize(base).unwrap_or(base.clone());
+            let mut report = benchmark.map(|b| Report::new(b, &rules, &base));
 
             // Run the list of rules over the Compose File
             for cf in compose_files.iter() {
                 debug!("Compose File :: {}", cf.path.display());
-                results.extend(rules.run(cf));
+                let errors = rules.errors().len();
+                let alerts = rules.run(cf);
+                if let Some(report) = report.as_mut() {
+                    report.add(cf, &alerts, &rules.errors()[errors..]);
+                }
+                results.extend(alerts);
             }
+            // Base files are part of every overlay project
+            security::dedup(&mut results);
 
-            let alert_present = match format.as_str() {
-                "cli" => {
-                    debug!("Running in CLI mode...");
-                    output_cli(&config, severity, results)?
+            // Rule errors are reported with the parse errors
+            diagnostics.extend(rules.errors().iter().cloned());
+            let scan_failed = parse_failed || !rules.errors().is_empty();
+
+            if let Some(mut report) = report {
+                report.add_errors(&diagnostics);
+                match output {
+                    Some(o) if format == "json" => {
+                        report.write(o)?;
+                        info!("Benchmark report written to: {}", o.display());
+                    }
+                    _ => output_benchmark(&report, &diagnostics),
                 }
-                "sarif" => {
-                    info!("Running in SARIF mode...");
-                    match output {
-                        Some(o) => {
-                            let sarif = SarifFile::new()
-                                .set_tool(String::from("Quibble"), VERSION_NUMBER.to_string())
-                                .base(base)
-                                .add_results(results)
-                                .build()?;
-
-                            sarif.write(o)?;
-                            info!("SARIF file written to: {}", o.display());
-                            true
-                        }
-                        None => {
-                            error!("No output file specified...");
-                            false
-                        }
+
+                if *disable_fail {
+                    return Ok(ExitStatus::Success);
+                }
+                return Ok(match (scan_failed, report.failed > 0) {
+                    (true, _) => ExitStatus::ScanError,
+                    (false, true) => ExitStatus::Findings,
+                    (false, false) => ExitStatus::Success,
+                });
+            }
+
+            if let Some(write_baseline) = write_baseline {
+                let baseline = Baseline::new(&results, &base);
+                baseline.write(write_baseline)?;
+                info!(
+                    "Baseline with {} finding(s) written to: {}",
+                    baseline.findings.len(),
+                    write_baseline.display()
+                );
+                return Ok(match scan_failed {
+                    true => ExitStatus::ScanError,
+                    false => ExitStatus::Success,
+                });
+            }
+
+            let fixed = match baseline {
+                Some(baseline) => match Baseline::load(baseline) {
+                    Ok(baseline) => baseline.compare(&mut results, &base),
+                    Err(err) => {
+                        error!("{err:#}");
+                        return Ok(ExitStatus::Usage);
                     }
+                },
+                None => Vec::new(),
+            };
+
+            if *fix {
+                let mut fixer = Fixer::new();
+                let applied: Vec<bool> = results
+                    .iter()
+                    .map(|alert| match &alert.fix {
+                        Some(fix) if reportable(severity, alert) => fixer.add(fix),
+                        _ => false,
+                    })
+                    .collect();
+                let count = applied.iter().filter(|applied| **applied).count();
+
+                // Secrets in the context of the diffs are redacted
+                for alert in results
+                    .iter()
+                    .filter(|alert| secrets::SECRET_ALERTS.contains(&alert.id.to_string().as_str()))
+                {
+                    fixer.redact(&alert.path);
+                }
+
+                for file in fixer.apply()? {
+                    match dry_run {
+                        true => print!("{}", file.diff(&base)),
+                        false => file.write()?,
+                    }
+                }
+
+                if *dry_run {
+                    info!("{count} alert(s) can be fixed");
+                } else {
+                    // Fixed alerts are no longer reported
+                    let mut applied = applied.into_iter();
+                    results.retain(|_| !applied.next().unwrap_or(false));
+                    info!("Fixed {count} alert(s)");
+                }
+            }
+
+            let alert_present = results.iter().any(|a| reportable(severity, a));
+
+            match output {
+                Some(o) if format == "sarif" => {
+                    info!("Running in SARIF mode...");
+                    let sarif = SarifFile::new()
+                        .set_tool(String::from("Quibble"), VERSION_NUMBER.to_string())
+                        .base(&base)
+                        .add_rules(rules.metadata())
+                        .add_results(results)
+                        .add_fixed(fixed)
+                        .add_diagnostics(diagnostics)
+                        .build()?;
+
+                    sarif.write(o)?;
+                    info!("SARIF file written to: {}", o.display());
                 }
                 _ => {
-                    error!("Unknown format output: `{format}`");
-                    true
+                    debug!("Running in CLI mode...");
+                    output_cli(
+                        &config,
+                        &rules.metadata(),
+                        severity,
+                        results,
+                        &diagnostics,
+                        &fixed,
+                    )?;
                 }
-            };
+            }
+
+            if *disable_fail {
+                return Ok(ExitStatus::Success);
+            }
+            return Ok(match (scan_failed, alert_present) {
+                (true, _) => ExitStatus::ScanError,
+                (false, true) => ExitStatus::Findings,
+                (false, false) => ExitStatus::Success,
+            });
+        }
+        ArgumentCommands::Rules { format } => {
+            let rules = Rules::new(config.clone());
 
-            if alert_present && !*disable_fail {
-                process::exit(1);
+            match format.as_str() {
+                "cli" => output_rules(&rules),
+                "markdown" => print!("{}", rules.markdown()),
+                _ => {
+                    error!("Unknown format output: `{format}`");
+                    return Ok(ExitStatus::Usage);
+                }
             }
         }
         ArgumentCommands::Registry { registry, image } => {
@@ -162,5 +635,5 @@ fn main() -> Result<()> {
         }
     }
 
-    Ok(())
+    Ok(ExitStatus::Success)
 }
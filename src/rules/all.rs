@@ -2,27 +2,46 @@ use anyhow::Result;
 use log::debug;
 
 use crate::{
-    compose::{ComposeFile, ListOrHashMap, StringOrNumber},
+    compose::{edit::Change, ComposeFile, ListOrHashMap, StringOrNumber},
     config::Config,
+    rules::RuleMetadata,
     security::{Alert, AlertLocation, RuleID, Severity},
 };
 
+/// Alerts of the `privileged` rule
+pub fn privileged_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("PRIVILEGED_CONTAINER".to_string()), "Privileged container")
+            .description("Privileged containers have every capability and access to all host devices, so escaping to the host is trivial.")
+            .severity(Severity::High)
+            .tags(&["security"])
+            .cwe("250")
+            .cwe("269")
+            .owasp("D04")
+            .cis("5.5")
+            .remediation("Remove `privileged: true` and add only the capabilities or devices the service needs."),
+    ]
+}
+
 pub fn privileged(
     _config: &Config,
     compose_file: &ComposeFile,
     alerts: &mut Vec<crate::security::Alert>,
 ) -> Result<()> {
-    for service in compose_file.compose.services.values() {
+    for (name, service) in &compose_file.compose.services {
         if let Some(privilege) = &service.privileged {
             if *privilege {
                 alerts.push(Alert {
                     id: RuleID::Quibble("PRIVILEGED_CONTAINER".to_string()),
-                    details: String::from("Container privilege enabled"),
+                    details: format!("Container privilege enabled for '{name}'"),
                     severity: Severity::High,
-                    path: AlertLocation {
-                        path: compose_file.path.clone(),
-                        ..Default::default()
-                    },
+                    path: compose_file.location(&format!("services.{name}.privileged")),
+                    value: Some(privilege.to_string()),
+                    fix: compose_file.fix(
+                        &format!("services.{name}.privileged"),
+                        "Remove `privileged: true`",
+                        Change::Remove,
+                    ),
                     ..Default::default()
                 })
             }
@@ -31,6 +50,44 @@ pub fn privileged(
     Ok(())
 }
 
+/// Alerts of the `security_opts` rule
+pub fn security_opts_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("SECURITY_OPTS".to_string()), "`no-new-privileges` not enabled")
+            .description("Without `no-new-privileges`, processes in the container can gain privileges through setuid binaries.")
+            .severity(Severity::High)
+            .tags(&["security", "hardening"])
+            .cwe("269")
+            .owasp("D04")
+            .cis("5.26")
+            .remediation("Add `no-new-privileges:true` to the service `security_opt`."),
+        RuleMetadata::new(RuleID::Quibble("SECCOMP_UNCONFINED".to_string()), "Seccomp profile disabled")
+            .description("`seccomp:unconfined` lets the container make every system call, including the ones the default profile blocks to prevent escapes.")
+            .severity(Severity::High)
+            .tags(&["security"])
+            .cwe("693")
+            .owasp("D04")
+            .cis("5.22")
+            .remediation("Remove `seccomp:unconfined`, or use a custom seccomp profile that allows only the calls the service needs."),
+        RuleMetadata::new(RuleID::Quibble("APPARMOR_UNCONFINED".to_string()), "AppArmor profile disabled")
+            .description("`apparmor:unconfined` runs the container without the AppArmor profile that restricts its access to the host.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("693")
+            .owasp("D04")
+            .cis("5.2")
+            .remediation("Remove `apparmor:unconfined`, or use a custom AppArmor profile."),
+        RuleMetadata::new(RuleID::Quibble("SELINUX_DISABLED".to_string()), "SELinux labels disabled")
+            .description("`label:disable` turns off SELinux separation of the container on hosts that enforce SELinux.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("693")
+            .owasp("D04")
+            .cis("5.3")
+            .remediation("Remove `label:disable`, and set the SELinux labels the service needs."),
+    ]
+}
+
 /// Security Opts Rule
 pub fn security_opts(
     _config: &Config,
@@ -38,82 +95,145 @@ pub fn security_opts(
     alerts: &mut Vec<crate::security::Alert>,
 ) -> Result<()> {
     for (name, service) in &compose_file.compose.services {
-        if let Some(secopts) = &service.security_opt {
-            let mapping = compose_file
-                .mappings
-                .get(format!("services.{}.security_opt", name).as_str());
+        let secopts = service.security_opt.as_deref().unwrap_or_default();
 
-            for secopt in secopts {
-                if secopt.starts_with("no-new-privileges") && secopt.ends_with("false") {
-                    alerts.push(Alert {
-                        id: RuleID::Quibble("SECURITY_OPTS".to_string()),
-                        details: format!(
-                            "Security Opts `no-new-privileges` set to `false` for '{service}'"
-                        ),
-                        severity: Severity::High,
-                        path: AlertLocation {
-                            path: compose_file.path.clone(),
-                            line: mapping.copied(),
-                        },
-                    })
-                }
+        for (index, secopt) in secopts.iter().enumerate() {
+            let key = format!("services.{name}.security_opt[{index}]");
+
+            if secopt.starts_with("no-new-privileges") && secopt.ends_with("false") {
+                alerts.push(Alert {
+                    id: RuleID::Quibble("SECURITY_OPTS".to_string()),
+                    details: format!(
+                        "Security Opts `no-new-privileges` set to `false` for '{service}'"
+                    ),
+                    severity: Severity::High,
+                    path: compose_file.location(&key),
+                    value: Some(secopt.clone()),
+                    fix: compose_file.fix(
+                        &key,
+                        "Set `no-new-privileges:true`",
+                        Change::Set(String::from("no-new-privileges:true")),
+                    ),
+                    ..Default::default()
+                })
             }
-        } else {
-            let mapping = compose_file
-                .mappings
-                .get(format!("services.{}", name).as_str());
 
+            // Options are `name:value` or `name=value`
+            let profile = secopt.split_once([':', '=']);
+            let (id, severity) = match profile {
+                Some(("seccomp", "unconfined")) => ("SECCOMP_UNCONFINED", Severity::High),
+                Some(("apparmor", "unconfined")) => ("APPARMOR_UNCONFINED", Severity::Medium),
+                Some(("label", "disable")) => ("SELINUX_DISABLED", Severity::Medium),
+                _ => continue,
+            };
+            alerts.push(Alert {
+                id: RuleID::Quibble(id.to_string()),
+                details: format!(
+                    "Security Opts `{secopt}` disables a security profile for '{name}'"
+                ),
+                severity,
+                path: compose_file.location(&key),
+                value: Some(secopt.clone()),
+                ..Default::default()
+            })
+        }
+
+        if !secopts.iter().any(|s| s.starts_with("no-new-privileges")) {
             alerts.push(Alert {
                 id: RuleID::Quibble("SECURITY_OPTS".to_string()),
                 details: format!("Security Opts `no-new-privileges` not set for '{service}'"),
                 severity: Severity::High,
-                path: AlertLocation {
-                    path: compose_file.path.clone(),
-                    line: mapping.copied(),
-                },
+                path: compose_file.location(&format!("services.{name}")),
+                fix: compose_file.fix(
+                    &format!("services.{name}.security_opt"),
+                    "Add `no-new-privileges:true` to `security_opt`",
+                    Change::Append(String::from("no-new-privileges:true")),
+                ),
+                ..Default::default()
             })
         }
     }
     Ok(())
 }
 
+/// Alerts of the `kernel_parameters` rule
+pub fn kernel_parameters_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("KERNEL_PARAMETERS".to_string()), "Kernel parameters or capabilities changed")
+            .description("The service changes kernel parameters (`sysctls`) or adds Linux capabilities (`cap_add`).")
+            .severity(Severity::Information)
+            .tags(&["security"])
+            .owasp("D04")
+            .remediation("Review that every kernel parameter and capability is needed."),
+        RuleMetadata::new(RuleID::Quibble("NET_ADMIN".to_string()), "`NET_ADMIN` capability")
+            .description("`NET_ADMIN` lets the container reconfigure networking, including interfaces, routes and firewall rules.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("250")
+            .owasp("D04")
+            .cis("5.4")
+            .remediation("Remove `NET_ADMIN` from `cap_add` unless the service manages networking."),
+        RuleMetadata::new(RuleID::Quibble("SYS_ADMIN".to_string()), "`SYS_ADMIN` capability")
+            .description("`SYS_ADMIN` allows a wide range of privileged operations, such as mounting filesystems, and is close to a privileged container.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("250")
+            .owasp("D04")
+            .cis("5.4")
+            .remediation("Remove `SYS_ADMIN` from `cap_add` and grant narrower capabilities."),
+        RuleMetadata::new(RuleID::Quibble("ALL".to_string()), "All capabilities added")
+            .description("`cap_add: [ALL]` gives the container every Linux capability.")
+            .severity(Severity::High)
+            .tags(&["security"])
+            .cwe("250")
+            .owasp("D04")
+            .cis("5.4")
+            .remediation("Add only the capabilities the service needs, and drop the rest with `cap_drop: [ALL]`."),
+    ]
+}
+
 pub fn kernel_parameters(
     _config: &Config,
     compose_file: &ComposeFile,
     alerts: &mut Vec<crate::security::Alert>,
 ) -> Result<()> {
-    for service in compose_file.compose.services.values() {
+    for (name, service) in &compose_file.compose.services {
         if let Some(syscalls) = &service.sysctls {
+            let location = compose_file.location(&format!("services.{name}.sysctls"));
+
             alerts.push(Alert {
                 id: RuleID::Quibble("KERNEL_PARAMETERS".to_string()),
-                details: String::from("Enabling extra syscalls"),
+                details: format!("Enabling extra syscalls for '{name}'"),
+                path: location.clone(),
                 ..Default::default()
             });
 
             fn syscall_check(
-                syscall: &String,
-                compose_file: &ComposeFile,
+                name: &str,
+                syscall: &str,
+                location: AlertLocation,
                 alerts: &mut Vec<crate::security::Alert>,
             ) {
                 if syscall.starts_with("net.ipv4.conf.all") {
                     alerts.push(Alert {
                         id: RuleID::Quibble("KERNEL_PARAMETERS".to_string()),
-                        details: format!("IPv4 Kernel Parameters modified: {syscall}"),
+                        details: format!("IPv4 Kernel Parameters modified for '{name}': {syscall}"),
                         severity: Severity::Information,
-                        path: AlertLocation {
-                            path: compose_file.path.clone(),
-                            ..Default::default()
-                        },
+                        path: location,
+                        value: Some(syscall.to_string()),
+                        ..Default::default()
                     })
                 }
             }
 
             match syscalls {
                 ListOrHashMap::Vec(v) => {
-                    for syscall in v {
+                    for (index, syscall) in v.iter().enumerate() {
                         match syscall {
                             StringOrNumber::Str(syscall) => {
-                                syscall_check(syscall, compose_file, alerts);
+                                let location = compose_file
+                                    .location(&format!("services.{name}.sysctls[{index}]"));
+                                syscall_check(name, syscall, location, alerts);
                             }
                             _ => {
                                 debug!("Unsupported syscall type: int / none")
@@ -123,55 +243,58 @@ pub fn kernel_parameters(
                 }
                 ListOrHashMap::Hash(h) => {
                     for syscall in h.keys() {
-                        syscall_check(syscall, compose_file, alerts);
+                        let location =
+                            compose_file.location(&format!("services.{name}.sysctls.{syscall}"));
+                        syscall_check(name, syscall, location, alerts);
                     }
                 }
             }
         }
 
         if let Some(capabilities) = &service.cap_add {
+            let location = compose_file.location(&format!("services.{name}.cap_add"));
+
             alerts.push(Alert {
                 id: RuleID::Quibble("KERNEL_PARAMETERS".to_string()),
-                details: String::from("Using extra Kernel Parameters"),
+                details: format!("Using extra Kernel Parameters for '{name}'"),
+                path: location.clone(),
                 ..Default::default()
             });
 
-            for cap in capabilities {
+            for (index, cap) in capabilities.iter().enumerate() {
+                let location = compose_file.location(&format!("services.{name}.cap_add[{index}]"));
+
                 // https://man7.org/linux/man-pages/man7/capabilities.7.html
                 // https://cloud.redhat.com/blog/increasing-security-of-istio-deployments-by-removing-the-need-for-privileged-containers
                 if cap.contains("NET_ADMIN") {
                     alerts.push(Alert {
                         id: RuleID::Quibble("NET_ADMIN".to_string()),
-                        details: String::from("Container with high networking privileages"),
+                        details: format!("Container with high networking privileages for '{name}'"),
                         severity: Severity::Medium,
-                        path: AlertLocation {
-                            path: compose_file.path.clone(),
-                            ..Default::default()
-                        },
+                        path: location.clone(),
+                        value: Some(cap.clone()),
+                        ..Default::default()
                     })
                 }
 
                 if cap.contains("SYS_ADMIN") {
                     alerts.push(Alert {
                         id: RuleID::Quibble("SYS_ADMIN".to_string()),
-                        details: String::from("Container with high system privileages"),
+                        details: format!("Container with high system privileages for '{name}'"),
                         severity: Severity::Medium,
-                        path: AlertLocation {
-                            path: compose_file.path.clone(),
-                            ..Default::default()
-                        },
+                        path: location.clone(),
+                        value: Some(cap.clone()),
+                        ..Default::default()
                     })
                 }
 
                 if cap.contains("ALL") {
                     alerts.push(Alert {
                         id: RuleID::Quibble("ALL".to_string()),
-                        details: String::from("All capabilities are enabled"),
+                        details: format!("All capabilities are enabled for '{name}'"),
                         severity: Severity::High,
-                        path: AlertLocation {
-                            path: compose_file.path.clone(),
-                            ..Default::default()
-                        },
+                        path: location.clone(),
+                        value: Some(cap.clone()),
                         ..Default::default()
                     })
                 }
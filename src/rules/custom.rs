@@ -0,0 +1,386 @@
+//! Declarative rules from the `custom-rules` of the config
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_yaml::Value;
+
+use crate::{
+    compose::ComposeFile,
+    config::{self, Config},
+    rules::{Rule, RuleMetadata},
+    security::{Alert, RuleID, Severity},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Part of a custom rule path
+enum Segment {
+    /// Mapping key (or `KEY=value` list item)
+    Key(String),
+    /// Sequence item
+    Index(usize),
+    /// Every key or item (`*`)
+    Any,
+}
+
+/// Custom rule from the config
+pub struct CustomRule {
+    rule: config::CustomRule,
+    metadata: Vec<RuleMetadata>,
+    path: Vec<Segment>,
+    regex: Option<Regex>,
+}
+
+impl CustomRule {
+    pub fn new(rule: &config::CustomRule) -> Result<Self> {
+        if rule.id.is_empty() {
+            return Err(anyhow!("custom rule is missing an `id`"));
+        }
+        let path = parse_path(&rule.path)?;
+        let regex = rule
+            .predicate
+            .regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()?;
+
+        let severity = rule
+            .severity
+            .clone()
+            .map(Severity::from)
+            .unwrap_or(Severity::Medium);
+        let mut metadata = RuleMetadata::new(
+            RuleID::Quibble(rule.id.clone()),
+            rule.title.clone().unwrap_or_else(|| rule.id.clone()),
+        )
+        .description(rule.description.clone().unwrap_or_default())
+        .severity(severity)
+        .tags(&["custom"])
+        .remediation(rule.remediation.clone().unwrap_or_default());
+        for cwe in &rule.cwe {
+            metadata = metadata.reference(RuleID::cwe(cwe)?);
+        }
+        for owasp in &rule.owasp {
+            metadata = metadata.reference(RuleID::owasp(owasp)?);
+        }
+
+        Ok(CustomRule {
+            rule: rule.clone(),
+            metadata: vec![metadata],
+            path,
+            regex,
+        })
+    }
+
+    /// Value matches the predicate of the rule
+    fn matches(&self, value: Option<&Value>) -> bool {
+        let predicate = &self.rule.predicate;
+        let value = value.filter(|v| !v.is_null());
+        let text = value.and_then(scalar);
+
+        let mut matched = match predicate.exists {
+            Some(exists) => value.is_some() == exists,
+            None => value.is_some(),
+        };
+        if let Some(equals) = &predicate.equals {
+            matched &= text.is_some() && text == scalar(equals);
+        }
+        if let Some(regex) = &self.regex {
+            matched &= text.as_deref().map(|t| regex.is_match(t)).unwrap_or(false);
+        }
+        if let Some(list) = &predicate.in_list {
+            matched &= text.is_some() && list.iter().any(|item| scalar(item) == text);
+        }
+        let number = text.as_deref().and_then(|t| t.trim().parse::<f64>().ok());
+        if let Some(greater) = predicate.greater_than {
+            matched &= number.map(|n| n > greater).unwrap_or(false);
+        }
+        if let Some(less) = predicate.less_than {
+            matched &= number.map(|n| n < less).unwrap_or(false);
+        }
+        matched
+    }
+}
+
+impl Rule for CustomRule {
+    fn name(&self) -> &str {
+        &self.rule.id
+    }
+
+    fn metadata(&self) -> &[RuleMetadata] {
+        &self.metadata
+    }
+
+    fn run(
+        &self,
+        _config: &Config,
+        compose_file: &ComposeFile,
+        alerts: &mut Vec<Alert>,
+    ) -> Result<()> {
+        let mut values = Vec::new();
+        resolve(
+            Some(&compose_file.raw),
+            String::new(),
+            &self.path,
+            &mut values,
+        );
+
+        for (key, value) in values {
+            // Services of profiles that are not enabled
+            if key.starts_with("services.") && compose_file.service(&key).is_none() {
+                continue;
+            }
+            if self.matches(value.as_ref()) == self.rule.not {
+                continue;
+            }
+
+            let service = compose_file
+                .service(&key)
+                .map(|(name, _)| name.as_str())
+                .unwrap_or_default();
+            let value = value.as_ref().and_then(scalar);
+            let details = match &self.rule.message {
+                Some(message) => message
+                    .replace("{service}", service)
+                    .replace("{key}", &key)
+                    .replace("{value}", value.as_deref().unwrap_or_default()),
+                None => format!("{}: `{key}`", self.metadata[0].title),
+            };
+
+            alerts.push(Alert {
+                id: self.metadata[0].id.clone(),
+                details,
+                severity: self.metadata[0].severity,
+                path: compose_file.location(&key),
+                value,
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Parse a path such as `services.*.labels["com.company.owner"]` or
+/// `services.web.ports[0]`
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut chars = path.chars();
+    let mut current = String::new();
+
+    fn push(current: &mut String, segments: &mut Vec<Segment>) {
+        match current.as_str() {
+            "" => {}
+            "*" => segments.push(Segment::Any),
+            key => segments.push(Segment::Key(key.to_string())),
+        }
+        current.clear();
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => push(&mut current, &mut segments),
+            '[' => {
+                push(&mut current, &mut segments);
+                let mut inner = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    inner.push(c);
+                }
+                let inner = inner.trim();
+                let segment = if inner == "*" {
+                    Segment::Any
+                } else if let Ok(index) = inner.parse::<usize>() {
+                    Segment::Index(index)
+                } else if inner.len() >= 2 && (inner.starts_with('"') || inner.starts_with('\'')) {
+                    Segment::Key(inner[1..inner.len() - 1].to_string())
+                } else {
+                    return Err(anyhow!("invalid path segment `[{inner}]` in `{path}`"));
+                };
+                segments.push(segment);
+            }
+            _ => current.push(c),
+        }
+    }
+    push(&mut current, &mut segments);
+
+    if segments.is_empty() {
+        return Err(anyhow!("custom rule is missing a `path`"));
+    }
+    Ok(segments)
+}
+
+/// Values (and their compose keys) at a path, missing values are `None`
+fn resolve(
+    value: Option<&Value>,
+    key: String,
+    path: &[Segment],
+    values: &mut Vec<(String, Option<Value>)>,
+) {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            values.push((key, value.cloned()));
+            return;
+        }
+    };
+    let child = |name: &str| match key.is_empty() {
+        true => name.to_string(),
+        false => format!("{key}.{name}"),
+    };
+
+    match (segment, value) {
+        (Segment::Key(name), Some(Value::Mapping(mapping))) => {
+            resolve(mapping.get(name.as_str()), child(name), rest, values)
+        }
+        // `KEY=value` list items, such as `environment` and `labels`
+        (Segment::Key(name), Some(Value::Sequence(items))) => {
+            let item =
+                items
+                    .iter()
+                    .filter_map(|i| i.as_str())
+                    .find_map(|i| match i.split_once('=') {
+                        Some((k, v)) if k == name => Some(Value::from(v)),
+                        None if i == name => Some(Value::Null),
+                        _ => None,
+                    });
+            resolve(item.as_ref(), child(name), rest, values)
+        }
+        (Segment::Key(name), _) => resolve(None, child(name), rest, values),
+        (Segment::Index(index), Some(Value::Sequence(items))) => {
+            resolve(items.get(*index), format!("{key}[{index}]"), rest, values)
+        }
+        (Segment::Index(index), _) => resolve(None, format!("{key}[{index}]"), rest, values),
+        (Segment::Any, Some(Value::Mapping(mapping))) => {
+            for (name, item) in mapping {
+                if let Some(name) = scalar(name) {
+                    resolve(Some(item), child(&name), rest, values);
+                }
+            }
+        }
+        (Segment::Any, Some(Value::Sequence(items))) => {
+            for (index, item) in items.iter().enumerate() {
+                resolve(Some(item), format!("{key}[{index}]"), rest, values);
+            }
+        }
+        (Segment::Any, _) => {}
+    }
+}
+
+/// String form of the scalar value at a compose key (`services.web.image`)
+pub fn value(compose_file: &ComposeFile, key: &str) -> Option<String> {
+    let path = parse_path(key).ok()?;
+    let mut values = Vec::new();
+    resolve(Some(&compose_file.raw), String::new(), &path, &mut values);
+    values.pop()?.1.as_ref().and_then(scalar)
+}
+
+/// String form of a scalar value
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::compose_file;
+
+    fn custom(yaml: &str) -> CustomRule {
+        CustomRule::new(&serde_yaml::from_str(yaml).unwrap()).unwrap()
+    }
+
+    fn run(rule: &CustomRule, compose_file: &ComposeFile) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        rule.run(&Config::default(), compose_file, &mut alerts)
+            .unwrap();
+        alerts
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            parse_path("services.*.labels[\"com.company.owner\"]").unwrap(),
+            vec![
+                Segment::Key(String::from("services")),
+                Segment::Any,
+                Segment::Key(String::from("labels")),
+                Segment::Key(String::from("com.company.owner")),
+            ]
+        );
+        assert_eq!(
+            parse_path("services.web.ports[0]").unwrap(),
+            vec![
+                Segment::Key(String::from("services")),
+                Segment::Key(String::from("web")),
+                Segment::Key(String::from("ports")),
+                Segment::Index(0),
+            ]
+        );
+        assert!(parse_path("services[web]").is_err());
+    }
+
+    #[test]
+    fn references() {
+        let rule = custom("id: APPROVED_REGISTRY\npath: services.*.image\nregex: ^registry\\.example\\.com/\ncwe: [CWE-829]\nowasp: [d08]\n");
+        assert_eq!(
+            rule.metadata()[0].references,
+            vec![
+                RuleID::Cwe(String::from("829")),
+                RuleID::Owasp(String::from("D08"))
+            ]
+        );
+
+        let invalid = serde_yaml::from_str("id: LOGGING\npath: services.*.logging\nowasp: [D11]\n");
+        assert!(CustomRule::new(&invalid.unwrap()).is_err());
+    }
+
+    #[test]
+    fn predicates() {
+        let (_dir, compose_file) = compose_file("services:\n  web:\n    image: someuser/web:1.0\n    logging:\n      driver: json-file\n    labels:\n      com.company.owner: web-team\n    mem_limit: 512\n  db:\n    image: postgres:16\n    labels:\n      - other=1\n    mem_limit: 4096\n",
+        );
+
+        let rule = custom("id: LOGGING_DRIVER\nmessage: Service `{service}` has no logging driver\npath: services.*.logging.driver\nexists: false\n");
+        let alerts = run(&rule, &compose_file);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].details, "Service `db` has no logging driver");
+        assert_eq!(alerts[0].path.line(), Some(8));
+
+        let rule = custom(
+            "id: SOMEUSER_IMAGE\npath: services.*.image\nregex: ^someuser/\nseverity: high\n",
+        );
+        let alerts = run(&rule, &compose_file);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::High);
+        assert_eq!(alerts[0].path.key.as_deref(), Some("services.web.image"));
+
+        let rule = custom(
+            "id: OWNER_LABEL\npath: services.*.labels[\"com.company.owner\"]\nexists: false\n",
+        );
+        let alerts = run(&rule, &compose_file);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(
+            alerts[0].path.key.as_deref(),
+            Some("services.db.labels.com.company.owner")
+        );
+
+        let rule = custom(
+            "id: MEMORY\npath: services.*.mem_limit\ngreater-than: 1024\nmessage: '{value}'\n",
+        );
+        let alerts = run(&rule, &compose_file);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].details, "4096");
+
+        let rule =
+            custom("id: APPROVED_IMAGES\npath: services.*.image\nin: [postgres:16]\nnot: true\n");
+        let alerts = run(&rule, &compose_file);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].path.key.as_deref(), Some("services.web.image"));
+
+        let rule = custom("id: DB_IMAGE\npath: services.db.image\nequals: postgres:16\n");
+        assert_eq!(run(&rule, &compose_file).len(), 1);
+    }
+}
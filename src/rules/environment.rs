@@ -1,73 +1,113 @@
 use anyhow::Result;
 use log::debug;
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use crate::{
-    compose::{ComposeFile, ListOrHashMap, StringOrNumber},
+    compose::{discovery, dotenv, ComposeFile, ListOrHashMap, ServiceEnvFile, StringOrNumber},
     config::Config,
-    security::{Alert, AlertLocation, RuleID, Severity},
+    rules::RuleMetadata,
+    secrets::{Detection, Scanner},
+    security::{Alert, AlertLocation, Region, RuleID, Severity},
 };
 
-/// Check environment variables for sensitive information
+/// Check environment variables for sensitive information, `source` names
+/// where the variable is set (`for 'web'`)
 fn check_environment(
-    compose_file: &ComposeFile,
+    scanner: &Scanner,
     alerts: &mut Vec<crate::security::Alert>,
-    service_name: &String,
-    key: String,
-    _value: String,
+    location: AlertLocation,
+    key: &str,
+    value: &str,
+    raw: Option<&str>,
+    source: &str,
 ) {
     if key.contains("DEBUG") {
-        let mapping = compose_file
-            .mappings
-            .get(format!("services.{}.environment.{}", service_name, key).as_str());
-
         alerts.push(Alert {
-            id: RuleID::Cwe(String::from("1244")),
+            id: RuleID::Quibble("DEBUG_ENABLED".to_string()),
             details: String::from("Debugging enabled in the container"),
             severity: Severity::Medium,
-            path: AlertLocation {
-                path: compose_file.path.clone(),
-                line: mapping.copied(),
-            },
+            path: location.clone(),
+            value: Some(value.to_string()),
+            ..Default::default()
         })
     }
-    // TODO: better way of detecting this
-    if key.contains("PASSWORD") || key.contains("KEY") || key.contains("TOKEN") {
-        let mapping = compose_file
-            .mappings
-            .get(format!("services.{}.environment.{}", service_name, key).as_str());
 
+    // Secrets that come from interpolation are not in the compose file
+    if let Some(secret) = scanner.scan(key, raw.unwrap_or(value)) {
+        let (id, severity) = match secret.detection {
+            Detection::Pattern => ("HARDCODED_SECRET", Severity::High),
+            Detection::Name | Detection::Entropy => ("POSSIBLE_SECRET", Severity::Medium),
+        };
         alerts.push(Alert {
-            id: RuleID::Cwe(String::from("215")),
-            details: String::from("Possible Hardcoded password"),
-            severity: Severity::Low,
-            path: AlertLocation {
-                path: compose_file.path.clone(),
-                line: mapping.copied(),
-            },
+            id: RuleID::Quibble(id.to_string()),
+            details: format!("{} in `{key}` {source}", secret.kind),
+            severity,
+            path: location,
+            value: Some(secret.redacted),
+            ..Default::default()
         })
     }
 }
 
+/// Alerts of the `environment_variables` rule
+pub fn environment_variables_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("DEBUG_ENABLED".to_string()), "Debugging enabled")
+            .description("An environment variable enables debugging in the container, which can expose internal state.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("489")
+            .cwe("215")
+            .owasp("D04")
+            .remediation("Disable debugging for deployed services."),
+        RuleMetadata::new(RuleID::Quibble("HARDCODED_SECRET".to_string()), "Hardcoded secret")
+            .description("An environment variable is set to a secret in the compose file, such as an AWS, GitHub, GitLab, Slack or Stripe key, a private key, a JWT or a password in a URL. The secret is redacted in the output.")
+            .severity(Severity::High)
+            .tags(&["security", "secrets"])
+            .cwe("798")
+            .owasp("D06")
+            .remediation("Revoke the secret, and use Compose `secrets` or variable interpolation from an untracked `.env` file."),
+        RuleMetadata::new(RuleID::Quibble("POSSIBLE_SECRET".to_string()), "Possible hardcoded secret")
+            .description("An environment variable that is named like a password, key or token, or has a random looking value, is set in the compose file. Placeholders (`changeme`) and interpolated values (`${DB_PASSWORD}`) are not reported.")
+            .severity(Severity::Medium)
+            .tags(&["security", "secrets"])
+            .cwe("798")
+            .owasp("D06")
+            .remediation("Use Compose `secrets` or variable interpolation from an untracked `.env` file, or add the value to the `secrets` allowlist of the config."),
+    ]
+}
+
 /// Environment Variable rules
 pub fn environment_variables(
-    _config: &Config,
+    config: &Config,
     compose_file: &ComposeFile,
     alerts: &mut Vec<crate::security::Alert>,
 ) -> Result<()> {
+    let scanner = Scanner::new(&config.secrets)?;
+
     for (name, service) in &compose_file.compose.services {
         if let Some(envvars) = &service.environment {
             match envvars {
                 ListOrHashMap::Vec(envvec) => {
-                    for envvar in envvec {
+                    for (index, envvar) in envvec.iter().enumerate() {
                         match envvar {
                             StringOrNumber::Str(str) => {
                                 if let Some((key, value)) = str.split_once('=') {
+                                    let raw = compose_file
+                                        .interpolated
+                                        .get(&format!("services.{name}.environment[{index}]"))
+                                        .and_then(|raw| raw.split_once('='))
+                                        .map(|(_, raw)| raw);
                                     check_environment(
-                                        compose_file,
+                                        &scanner,
                                         alerts,
-                                        name,
-                                        key.to_string(),
-                                        value.to_string(),
+                                        compose_file.location(&format!(
+                                            "services.{name}.environment.{key}"
+                                        )),
+                                        key,
+                                        value,
+                                        raw,
+                                        &format!("for '{name}'"),
                                     )
                                 }
                             }
@@ -82,12 +122,18 @@ pub fn environment_variables(
                     for (key, value) in envhash {
                         match value {
                             StringOrNumber::Str(value) => {
+                                let raw = compose_file
+                                    .interpolated
+                                    .get(&format!("services.{name}.environment.{key}"));
                                 check_environment(
-                                    compose_file,
+                                    &scanner,
                                     alerts,
-                                    name,
-                                    key.to_string(),
-                                    value.to_string(),
+                                    compose_file
+                                        .location(&format!("services.{name}.environment.{key}")),
+                                    key,
+                                    value,
+                                    raw.map(String::as_str),
+                                    &format!("for '{name}'"),
                                 );
                             }
                             _ => {
@@ -101,3 +147,138 @@ pub fn environment_variables(
     }
     Ok(())
 }
+
+/// Alerts of the `env_files` rule
+pub fn env_files_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("ENV_FILE_MISSING".to_string()), "Env file missing")
+            .description("A required `env_file` of a service does not exist, so Compose fails to start the service. The variables of env files are checked like `environment` variables for secrets and debugging.")
+            .severity(Severity::Medium)
+            .tags(&["security", "secrets"])
+            .owasp("D04")
+            .remediation("Create the env file, or set `required: false` on the `env_file` entry when it is optional."),
+        RuleMetadata::new(RuleID::Quibble("ENV_FILE_WORLD_READABLE".to_string()), "Env file readable by all users")
+            .description("An `env_file` of a service can be read by every user on the host, exposing the secrets it holds.")
+            .severity(Severity::Medium)
+            .tags(&["security", "secrets"])
+            .cwe("732")
+            .owasp("D06")
+            .remediation("Restrict the permissions of the env file (`chmod 600`)."),
+        RuleMetadata::new(RuleID::Quibble("ENV_FILE_NOT_IGNORED".to_string()), "Env file not ignored by git")
+            .description("An `env_file` of a service is in a git repository but not covered by `.gitignore`, so its secrets can be committed.")
+            .severity(Severity::Low)
+            .tags(&["security", "secrets"])
+            .cwe("538")
+            .owasp("D06")
+            .remediation("Add the env file to `.gitignore`, and commit an example file without values instead."),
+    ]
+}
+
+/// Env File rules, the entries of env files are checked like `environment`
+pub fn env_files(
+    config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<crate::security::Alert>,
+) -> Result<()> {
+    let scanner = Scanner::new(&config.secrets)?;
+
+    // Files shared by services are only checked once
+    let mut files: BTreeMap<PathBuf, Vec<ServiceEnvFile>> = BTreeMap::new();
+    for env_file in compose_file.env_files() {
+        files
+            .entry(env_file.path.clone())
+            .or_default()
+            .push(env_file);
+    }
+
+    for (path, references) in files {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let first = &references[0];
+
+        if !path.is_file() {
+            for reference in references.iter().filter(|r| r.required) {
+                alerts.push(Alert {
+                    id: RuleID::Quibble("ENV_FILE_MISSING".to_string()),
+                    details: format!(
+                        "Env file `{name}` for '{}' does not exist",
+                        reference.service
+                    ),
+                    severity: Severity::Medium,
+                    path: reference.location.clone(),
+                    value: Some(path.display().to_string()),
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path)?.permissions().mode();
+            if mode & 0o004 != 0 {
+                alerts.push(Alert {
+                    id: RuleID::Quibble("ENV_FILE_WORLD_READABLE".to_string()),
+                    details: format!("Env file `{name}` is readable by all users"),
+                    severity: Severity::Medium,
+                    path: first.location.clone(),
+                    value: Some(format!("{:o}", mode & 0o777)),
+                    ..Default::default()
+                });
+            }
+        }
+
+        if discovery::git_ignored(&path) == Some(false) {
+            alerts.push(Alert {
+                id: RuleID::Quibble("ENV_FILE_NOT_IGNORED".to_string()),
+                details: format!("Env file `{name}` is not in `.gitignore`"),
+                severity: Severity::Low,
+                path: first.location.clone(),
+                value: Some(path.display().to_string()),
+                ..Default::default()
+            });
+        }
+
+        let data = fs::read_to_string(&path)?;
+        let lines: Vec<&str> = data.lines().collect();
+        // Alerts in the env file belong to a service when only one uses it
+        let mut services: Vec<&String> = references.iter().map(|r| &r.service).collect();
+        services.dedup();
+        let service = match services[..] {
+            [service] => Some(service.clone()),
+            _ => None,
+        };
+
+        let mut found = Vec::new();
+        for entry in dotenv::parse(&data) {
+            let length = lines.get(entry.line as usize).map_or(0, |l| l.len());
+            let location = AlertLocation {
+                path: path.clone(),
+                region: Some(Region {
+                    start_line: entry.line,
+                    start_column: 0,
+                    end_line: entry.line,
+                    end_column: length as i32,
+                }),
+                ..Default::default()
+            };
+            check_environment(
+                &scanner,
+                &mut found,
+                location,
+                &entry.key,
+                &entry.value,
+                None,
+                &format!("of `{name}`"),
+            );
+        }
+        alerts.extend(found.into_iter().map(|alert| Alert {
+            service: service.clone(),
+            ..alert
+        }));
+    }
+    Ok(())
+}
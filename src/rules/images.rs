@@ -3,9 +3,41 @@ use anyhow::Result;
 use crate::{
     compose::{ComposeFile, StringOrBuild},
     config::Config,
-    security::{Alert, AlertLocation, RuleID, Severity},
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
 };
 
+/// Alerts of the `container_images` rule
+pub fn container_images_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("IMAGE_TAG".to_string()), "Container image")
+            .description("Container image used by a service.")
+            .severity(Severity::Information)
+            .tags(&["information"])
+            .owasp("D08")
+            .remediation("No action needed, review that the image comes from a trusted source."),
+        RuleMetadata::new(RuleID::Quibble("BUILD_CONTEXT".to_string()), "Image built from a local context")
+            .description("The service image is built from a local build context.")
+            .severity(Severity::Information)
+            .tags(&["information"])
+            .owasp("D08")
+            .remediation("No action needed, review the Dockerfile in the build context."),
+        RuleMetadata::new(RuleID::Quibble("IMAGE_ENV_VAR".to_string()), "Image set from a variable")
+            .description("The container image is set by variable interpolation, so the image that runs depends on the environment.")
+            .severity(Severity::Information)
+            .tags(&["supply-chain"])
+            .owasp("D08")
+            .remediation("Make sure the variable can only be set by trusted users, or use a fixed image."),
+        RuleMetadata::new(RuleID::Quibble("IMAGE_TAG_LATEST".to_string()), "Rolling release image tag")
+            .description("Rolling tags (`latest`, `main`, `master`) change without notice, so updates can pull in untested or compromised images.")
+            .severity(Severity::Medium)
+            .tags(&["security", "supply-chain"])
+            .cwe("1357")
+            .owasp("D08")
+            .remediation("Pin the image to a version tag or digest (`image@sha256:...`)."),
+    ]
+}
+
 /// Container Images
 pub fn container_images(
     _config: &Config,
@@ -15,18 +47,14 @@ pub fn container_images(
     for (name, service) in &compose_file.compose.services {
         // Manually building project
         if let Some(build_enum) = &service.build {
-            let mapping_line = compose_file
-                .mappings
-                .get(format!("services.{}.build", name).as_str());
+            let location = compose_file.location(&format!("services.{name}.build"));
 
             match build_enum {
                 StringOrBuild::Str(context) => alerts.push(Alert {
                     id: RuleID::Quibble("BUILD_CONTEXT".to_string()),
                     details: format!("Build context path: {context}"),
-                    path: AlertLocation {
-                        path: compose_file.path.clone(),
-                        line: mapping_line.copied(),
-                    },
+                    path: location.clone(),
+                    value: Some(context.clone()),
                     ..Default::default()
                 }),
                 StringOrBuild::Build(build) => {
@@ -34,10 +62,8 @@ pub fn container_images(
                         alerts.push(Alert {
                             id: RuleID::Quibble("BUILD_CONTEXT".to_string()),
                             details: format!("Build context path: {context}"),
-                            path: AlertLocation {
-                                path: compose_file.path.clone(),
-                                line: mapping_line.copied(),
-                            },
+                            path: location.clone(),
+                            value: Some(context.clone()),
                             ..Default::default()
                         })
                     }
@@ -47,36 +73,41 @@ pub fn container_images(
 
         // Pulling remote image
         if let Some(image) = &service.image {
-            let mapping_line = compose_file
-                .mappings
-                .get(format!("services.{}.image", name).as_str());
+            let location = compose_file.location(&format!("services.{name}.image"));
 
             // Format strings
-            if image.contains("${") {
+            if let Some(raw) = compose_file
+                .interpolated
+                .get(&format!("services.{name}.image"))
+            {
                 alerts.push(Alert {
                     id: RuleID::Quibble("IMAGE_ENV_VAR".to_string()),
-                    details: format!("Container Image using Environment Variable: {image}"),
-                    path: AlertLocation {
-                        path: compose_file.path.clone(),
-                        line: mapping_line.copied(),
-                    },
+                    details: format!(
+                        "Container Image using Environment Variable: {raw} (resolved to `{image}`)"
+                    ),
+                    path: location.clone(),
+                    value: Some(raw.clone()),
                     ..Default::default()
                 })
-            } else if let Ok(container) = service.parse_image() {
+            }
+
+            if image.is_empty() {
+                continue;
+            }
+
+            if let Ok(container) = service.parse_image() {
                 alerts.push(Alert {
                     id: RuleID::Quibble("IMAGE_TAG".to_string()),
                     details: format!("Container Image: {container}"),
-                    path: AlertLocation {
-                        path: compose_file.path.clone(),
-                        line: mapping_line.copied(),
-                    },
+                    path: location.clone(),
+                    value: Some(image.clone()),
                     ..Default::default()
                 });
 
                 // Rule: Pinned to latest rolling container image
                 // - The main reason behind this is if you are using watchtower or other
                 // service to update containers it might cause issues
-                let latest = vec!["latest", "main", "master"];
+                let latest = ["latest", "main", "master"];
                 if latest.contains(&container.tag.as_str()) {
                     alerts.push(Alert {
                         id: RuleID::Quibble("IMAGE_TAG_LATEST".to_string()),
@@ -85,10 +116,8 @@ pub fn container_images(
                             container.tag
                         ),
                         severity: Severity::Medium,
-                        path: AlertLocation {
-                            path: compose_file.path.clone(),
-                            line: mapping_line.copied(),
-                        },
+                        path: location.clone(),
+                        value: Some(image.clone()),
                         ..Default::default()
                     });
                 }
@@ -0,0 +1,130 @@
+use std::fmt::Write;
+
+use crate::security::{Alert, RuleID, Severity};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// Description of an alert a rule reports
+pub struct RuleMetadata {
+    /// Stable alert ID (`PRIVILEGED_CONTAINER`)
+    pub id: RuleID,
+    /// Short, human readable name
+    pub title: String,
+    /// What the rule checks and why it matters
+    pub description: String,
+    /// Default severity of the alert
+    pub severity: Severity,
+    /// Tags (`security`, `hardening`, ...)
+    pub tags: Vec<String>,
+    /// CWE and OWASP Docker Top 10 references
+    pub references: Vec<RuleID>,
+    /// CIS Docker Benchmark controls the alert covers (`5.5`)
+    pub cis: Vec<String>,
+    /// How to fix the problem
+    pub remediation: String,
+    /// Only enabled by default with `--benchmark`
+    pub benchmark: bool,
+}
+
+impl RuleMetadata {
+    pub fn new(id: RuleID, title: impl ToString) -> Self {
+        RuleMetadata {
+            id,
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn description(mut self, description: impl ToString) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn cwe(mut self, cwe: &str) -> Self {
+        self.references.push(RuleID::Cwe(cwe.to_string()));
+        self
+    }
+
+    pub fn owasp(mut self, owasp: &str) -> Self {
+        self.references.push(RuleID::Owasp(owasp.to_string()));
+        self
+    }
+
+    /// CWE or OWASP Docker Top 10 reference
+    pub fn reference(mut self, reference: RuleID) -> Self {
+        self.references.push(reference);
+        self
+    }
+
+    /// OWASP Docker Top 10 categories of the alert
+    pub fn categories(&self) -> impl Iterator<Item = &RuleID> {
+        self.references
+            .iter()
+            .filter(|r| matches!(r, RuleID::Owasp(_)))
+    }
+
+    pub fn cis(mut self, control: &str) -> Self {
+        self.cis.push(control.to_string());
+        self
+    }
+
+    pub fn remediation(mut self, remediation: impl ToString) -> Self {
+        self.remediation = remediation.to_string();
+        self
+    }
+
+    /// Only enable the alert by default with `--benchmark`
+    pub fn benchmark(mut self) -> Self {
+        self.benchmark = true;
+        self
+    }
+
+    /// Alert with the ID and default severity of the metadata
+    pub fn alert(&self) -> Alert {
+        Alert {
+            id: self.id.clone(),
+            severity: self.severity,
+            ..Default::default()
+        }
+    }
+
+    /// Markdown documentation of the alert
+    pub fn markdown(&self) -> String {
+        let mut doc = format!("### `{}`: {}\n\n", self.id, self.title);
+        let _ = writeln!(doc, "{}\n", self.description);
+        let _ = writeln!(doc, "- **Severity:** {}", self.severity);
+        if !self.tags.is_empty() {
+            let _ = writeln!(doc, "- **Tags:** {}", self.tags.join(", "));
+        }
+        if !self.references.is_empty() {
+            let references: Vec<String> = self
+                .references
+                .iter()
+                .map(|r| match r.title() {
+                    Some(title) => format!("{r} ({title})"),
+                    None => r.to_string(),
+                })
+                .collect();
+            let _ = writeln!(doc, "- **References:** {}", references.join(", "));
+        }
+        if !self.cis.is_empty() {
+            let _ = writeln!(doc, "- **CIS Docker Benchmark:** {}", self.cis.join(", "));
+        }
+        if self.benchmark {
+            let _ = writeln!(doc, "- **Enabled:** with `--benchmark`");
+        }
+        if !self.remediation.is_empty() {
+            let _ = writeln!(doc, "\n**Remediation:** {}", self.remediation);
+        }
+        doc
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+
+use crate::{
+    compose::{edit::Change, ComposeFile, PortBinding, PortExposure},
+    config::Config,
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
+};
+
+/// Alerts of the `published_ports` rule
+pub fn published_ports_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("PORT_ALL_INTERFACES".to_string()), "Port published on all interfaces")
+            .description("Ports published without a host address listen on every interface of the host, and Docker bypasses host firewalls such as `ufw`.")
+            .severity(Severity::Low)
+            .tags(&["security", "network"])
+            .cwe("668")
+            .owasp("D03")
+            .cis("5.14")
+            .remediation("Bind the port to a host address, such as `127.0.0.1:8080:80`."),
+        RuleMetadata::new(RuleID::Quibble("HOST_NETWORK".to_string()), "Host networking")
+            .description("`network_mode: host` shares the host network stack, exposing every port the container listens on.")
+            .severity(Severity::Medium)
+            .tags(&["security", "network"])
+            .cwe("668")
+            .owasp("D03")
+            .owasp("D04")
+            .cis("5.10")
+            .remediation("Use a bridge network and publish only the ports that are needed."),
+    ]
+}
+
+/// Published Ports Rule
+///
+/// Ports published without a host address are reachable on every interface
+/// of the host, bypassing host firewalls such as `ufw`. The alert carries the
+/// fix that binds the port to `127.0.0.1` (CIS Docker Benchmark 5.14, traffic
+/// bound to a specific host interface).
+pub fn published_ports(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<crate::security::Alert>,
+) -> Result<()> {
+    for (name, service) in &compose_file.compose.services {
+        match service.exposure() {
+            PortExposure::NotPublished | PortExposure::Loopback => continue,
+            _ => {}
+        }
+
+        if service.network_mode.as_deref() == Some("host") {
+            alerts.push(Alert {
+                id: RuleID::Quibble("HOST_NETWORK".to_string()),
+                details: format!("Host networking exposes every port of '{service}'"),
+                severity: Severity::Medium,
+                path: compose_file.location(&format!("services.{name}.network_mode")),
+                value: service.network_mode.clone(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        if let Some(ports) = &service.ports {
+            for (index, port) in ports.iter().enumerate() {
+                if port.exposure() == PortExposure::AllInterfaces {
+                    let key = format!("services.{name}.ports[{index}]");
+                    let loopback = PortBinding {
+                        host_ip: Some(String::from("127.0.0.1")),
+                        ..port.clone()
+                    }
+                    .to_string();
+                    // `tcp` is the default protocol of the short syntax
+                    let loopback = loopback.strip_suffix("/tcp").unwrap_or(&loopback);
+                    alerts.push(Alert {
+                        id: RuleID::Quibble("PORT_ALL_INTERFACES".to_string()),
+                        details: format!(
+                            "Port `{port}` published on all interfaces for '{service}'"
+                        ),
+                        severity: Severity::Low,
+                        path: compose_file.location(&key),
+                        value: Some(port.to_string()),
+                        fix: compose_file.fix(
+                            &key,
+                            "Bind the port to `127.0.0.1`",
+                            Change::Set(loopback.to_string()),
+                        ),
+                        ..Default::default()
+                    })
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::compose_file;
+
+    #[test]
+    fn all_interfaces() {
+        let (_dir, compose_file) = compose_file("services:\n  web:\n    ports:\n      - 8080:80\n      - 127.0.0.1:8443:443\n      - 10.0.0.5:9000:9000\n      - 53:53/udp\n  db:\n    ports:\n      - 127.0.0.1:5432:5432\n  worker:\n    image: busybox\n",
+        );
+        let mut alerts = Vec::new();
+        published_ports(&Config::default(), &compose_file, &mut alerts).unwrap();
+
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts
+            .iter()
+            .all(|a| a.id == RuleID::Quibble("PORT_ALL_INTERFACES".to_string())));
+        assert_eq!(alerts[0].path.key.as_deref(), Some("services.web.ports[0]"));
+        assert_eq!(alerts[0].value.as_deref(), Some("8080:80/tcp"));
+        assert_eq!(alerts[1].path.key.as_deref(), Some("services.web.ports[3]"));
+
+        let fix = |alert: &Alert| alert.fix.as_ref().unwrap().edits[0].text.clone();
+        assert!(fix(&alerts[0]).contains("127.0.0.1:8080:80"));
+        assert!(!fix(&alerts[0]).contains("/tcp"));
+        assert!(fix(&alerts[1]).contains("127.0.0.1:53:53/udp"));
+    }
+
+    #[test]
+    fn host_network() {
+        let (_dir, compose_file) = compose_file(
+            "services:\n  web:\n    network_mode: host\n    ports:\n      - 8080:80\n",
+        );
+        let mut alerts = Vec::new();
+        published_ports(&Config::default(), &compose_file, &mut alerts).unwrap();
+
+        // Published ports are ignored with host networking
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].id, RuleID::Quibble("HOST_NETWORK".to_string()));
+        assert_eq!(
+            alerts[0].path.key.as_deref(),
+            Some("services.web.network_mode")
+        );
+        assert_eq!(alerts[0].value.as_deref(), Some("host"));
+    }
+}
@@ -3,9 +3,23 @@ use anyhow::Result;
 use crate::{
     compose::ComposeFile,
     config::Config,
-    security::{Alert, AlertLocation, RuleID, Severity},
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
 };
 
+/// Alerts of the `docker_registry` rule
+pub fn docker_registry_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("DOCKER_REGISTRY".to_string()), "Image from an untrusted registry")
+            .description("The container image is pulled from a registry that is not in the list of allowed `registries`.")
+            .severity(Severity::High)
+            .tags(&["security", "supply-chain"])
+            .cwe("829")
+            .owasp("D08")
+            .remediation("Use an image from a trusted registry, or add the registry to `registries` in the Quibble config."),
+    ]
+}
+
 /// Docker registry Rule
 ///
 /// Make sure that the container is being pulled from a trusted source
@@ -14,17 +28,19 @@ pub fn docker_registry(
     compose_file: &ComposeFile,
     alerts: &mut Vec<crate::security::Alert>,
 ) -> Result<()> {
-    for service in compose_file.compose.services.values() {
+    for (name, service) in &compose_file.compose.services {
         if let Ok(container) = service.parse_image() {
             if !config.registries.contains(&container.instance) {
                 alerts.push(Alert {
                     id: RuleID::Quibble("DOCKER_REGISTRY".to_string()),
-                    details: format!("Container from unknown registry: {}", &container.instance),
+                    details: format!(
+                        "Container from unknown registry for '{name}': {}",
+                        &container.instance
+                    ),
                     severity: Severity::High,
-                    path: AlertLocation {
-                        path: compose_file.path.clone(),
-                        ..Default::default()
-                    },
+                    path: compose_file.location(&format!("services.{name}.image")),
+                    value: service.image.clone(),
+                    ..Default::default()
                 });
             }
         }
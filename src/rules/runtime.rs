@@ -0,0 +1,423 @@
+//! Container runtime options, covering section 5 (Container Runtime) of the
+//! CIS Docker Benchmark
+use anyhow::Result;
+
+use crate::{
+    compose::{
+        ComposeFile, MountType, Propagation, Service, StringOrDevice, StringOrList, StringOrNumber,
+    },
+    config::Config,
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
+};
+
+/// Alert with the ID and default severity of its metadata
+fn alert(metadata: &[RuleMetadata], id: &str) -> Alert {
+    metadata
+        .iter()
+        .find(|m| m.id.to_string() == id)
+        .map(RuleMetadata::alert)
+        .unwrap_or_else(|| Alert {
+            id: RuleID::Quibble(id.to_string()),
+            ..Default::default()
+        })
+}
+
+/// Host directories that must not be mounted into containers
+const SENSITIVE_DIRECTORIES: &[&str] = &[
+    "/", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sys", "/usr",
+];
+
+/// Alerts of the `host_namespaces` rule
+pub fn host_namespaces_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("HOST_PID".to_string()), "Host process namespace shared")
+            .description("`pid: host` lets the container see and signal every process on the host.")
+            .severity(Severity::High)
+            .tags(&["security"])
+            .cwe("653")
+            .owasp("D04")
+            .cis("5.16")
+            .remediation("Remove `pid: host` from the service.")
+            .benchmark(),
+        RuleMetadata::new(RuleID::Quibble("HOST_IPC".to_string()), "Host IPC namespace shared")
+            .description("`ipc: host` gives the container access to the shared memory and semaphores of the host.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("653")
+            .owasp("D04")
+            .cis("5.17")
+            .remediation("Remove `ipc: host`, or share IPC with another service (`ipc: service:NAME`).")
+            .benchmark(),
+        RuleMetadata::new(RuleID::Quibble("HOST_UTS".to_string()), "Host UTS namespace shared")
+            .description("`uts: host` lets the container change the hostname of the host.")
+            .severity(Severity::Low)
+            .tags(&["security"])
+            .cwe("653")
+            .owasp("D04")
+            .cis("5.21")
+            .remediation("Remove `uts: host` from the service.")
+            .benchmark(),
+        RuleMetadata::new(RuleID::Quibble("HOST_USERNS".to_string()), "Host user namespace shared")
+            .description("`userns_mode: host` disables user namespace remapping, so root in the container is root on the host.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("653")
+            .owasp("D01")
+            .cis("5.31")
+            .remediation("Remove `userns_mode: host` from the service.")
+            .benchmark(),
+    ]
+}
+
+/// Host Namespaces Rule
+pub fn host_namespaces(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = host_namespaces_metadata();
+    for (name, service) in &compose_file.compose.services {
+        let namespaces = [
+            ("HOST_PID", "pid", &service.pid),
+            ("HOST_IPC", "ipc", &service.ipc),
+            ("HOST_UTS", "uts", &service.uts),
+            ("HOST_USERNS", "userns_mode", &service.userns_mode),
+        ];
+
+        for (id, key, value) in namespaces {
+            if value.as_deref() == Some("host") {
+                alerts.push(Alert {
+                    details: format!("Host namespace shared with `{key}: host` for '{name}'"),
+                    path: compose_file.location(&format!("services.{name}.{key}")),
+                    value: value.clone(),
+                    ..alert(&metadata, id)
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the `host_devices` rule
+pub fn host_devices_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("HOST_DEVICE".to_string()), "Host device exposed")
+            .description("Host devices give the container direct access to hardware, such as disks, and can be used to escape the container.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("653")
+            .owasp("D04")
+            .cis("5.18")
+            .remediation("Remove the device, or limit its permissions (`/dev/sda:/dev/xvda:r`).")
+            .benchmark(),
+    ]
+}
+
+/// Host Devices Rule
+pub fn host_devices(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = host_devices_metadata();
+    for (name, service) in &compose_file.compose.services {
+        for (index, device) in service.devices.iter().flatten().enumerate() {
+            let source = match device {
+                StringOrDevice::Str(device) => device.split(':').next().unwrap_or(device),
+                StringOrDevice::Device(device) => device.source.as_str(),
+            };
+            alerts.push(Alert {
+                details: format!("Host device `{source}` exposed to '{name}'"),
+                path: compose_file.location(&format!("services.{name}.devices[{index}]")),
+                value: Some(source.to_string()),
+                ..alert(&metadata, "HOST_DEVICE")
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the `host_mounts` rule
+pub fn host_mounts_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("SENSITIVE_HOST_MOUNT".to_string()), "Sensitive host directory mounted")
+            .description("System directories of the host (`/`, `/etc`, `/proc`, ...) are mounted into the container, which can read or change the configuration of the host.")
+            .severity(Severity::High)
+            .tags(&["security"])
+            .cwe("668")
+            .owasp("D04")
+            .cis("5.6")
+            .remediation("Mount only the files the service needs, read-only (`/etc/localtime:/etc/localtime:ro`).")
+            .benchmark(),
+        RuleMetadata::new(RuleID::Quibble("MOUNT_PROPAGATION_SHARED".to_string()), "Shared mount propagation")
+            .description("Mounts made in the container with `shared` propagation are visible on the host, and the other way around.")
+            .severity(Severity::Medium)
+            .tags(&["security"])
+            .cwe("668")
+            .owasp("D04")
+            .cis("5.20")
+            .remediation("Use the default (`rprivate`) bind propagation.")
+            .benchmark(),
+    ]
+}
+
+/// Host Mounts Rule
+pub fn host_mounts(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = host_mounts_metadata();
+    for (name, service) in &compose_file.compose.services {
+        for (index, volume) in service.volumes.iter().flatten().enumerate() {
+            if volume.mount_type != MountType::Bind {
+                continue;
+            }
+            let key = format!("services.{name}.volumes[{index}]");
+
+            if let Some(path) = volume.host_path() {
+                let directory = match path.trim_end_matches('/') {
+                    "" => "/",
+                    directory => directory,
+                };
+                if SENSITIVE_DIRECTORIES.contains(&directory) {
+                    alerts.push(Alert {
+                        details: format!("Host directory `{directory}` mounted into '{name}'"),
+                        path: compose_file.location(&key),
+                        value: Some(path.to_string()),
+                        ..alert(&metadata, "SENSITIVE_HOST_MOUNT")
+                    });
+                }
+            }
+
+            if let Some(Propagation::Shared | Propagation::RShared) = volume.propagation {
+                alerts.push(Alert {
+                    details: format!(
+                        "Mount `{}` uses shared propagation in '{name}'",
+                        volume.target
+                    ),
+                    path: compose_file.location(&key),
+                    value: Some(volume.to_string()),
+                    ..alert(&metadata, "MOUNT_PROPAGATION_SHARED")
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the `privileged_ports` rule
+pub fn privileged_ports_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("PRIVILEGED_PORT".to_string()), "Privileged host port")
+            .description("Host ports below 1024 are reserved for services run by root, publishing them can let the container impersonate a host service.")
+            .severity(Severity::Low)
+            .tags(&["security", "network"])
+            .owasp("D03")
+            .cis("5.8")
+            .remediation("Publish the port on a host port above 1024, behind a reverse proxy if needed.")
+            .benchmark(),
+    ]
+}
+
+/// Privileged Ports Rule
+pub fn privileged_ports(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = privileged_ports_metadata();
+    for (name, service) in &compose_file.compose.services {
+        for (index, port) in service.ports.iter().flatten().enumerate() {
+            match &port.published {
+                Some(published) if published.start < 1024 => {
+                    alerts.push(Alert {
+                        details: format!(
+                            "Privileged host port `{published}` published for '{name}'"
+                        ),
+                        path: compose_file.location(&format!("services.{name}.ports[{index}]")),
+                        value: Some(port.to_string()),
+                        ..alert(&metadata, "PRIVILEGED_PORT")
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the `resource_limits` rule
+pub fn resource_limits_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("MEMORY_LIMIT".to_string()), "No memory limit")
+            .description("Without a memory limit a single container can use all the memory of the host.")
+            .severity(Severity::Low)
+            .tags(&["hardening"])
+            .cwe("770")
+            .owasp("D07")
+            .cis("5.11")
+            .remediation("Set `mem_limit` or `deploy.resources.limits.memory`.")
+            .benchmark(),
+        RuleMetadata::new(RuleID::Quibble("CPU_LIMIT".to_string()), "No CPU limit")
+            .description("Without a CPU limit or share a single container can starve the other containers of the host.")
+            .severity(Severity::Low)
+            .tags(&["hardening"])
+            .cwe("770")
+            .owasp("D07")
+            .cis("5.12")
+            .remediation("Set `cpus`, `cpu_shares` or `deploy.resources.limits.cpus`.")
+            .benchmark(),
+        RuleMetadata::new(RuleID::Quibble("PIDS_LIMIT".to_string()), "No process limit")
+            .description("Without a process limit a fork bomb in the container can exhaust the process table of the host.")
+            .severity(Severity::Low)
+            .tags(&["hardening"])
+            .cwe("770")
+            .owasp("D07")
+            .cis("5.29")
+            .remediation("Set `pids_limit` or `deploy.resources.limits.pids`.")
+            .benchmark(),
+    ]
+}
+
+/// Limit is set, `0` and `-1` mean unlimited
+fn limited(value: Option<&StringOrNumber>) -> bool {
+    match value {
+        None | Some(StringOrNumber::None) | Some(StringOrNumber::Num(0)) => false,
+        Some(StringOrNumber::Int(value)) => *value > 0,
+        Some(StringOrNumber::Str(value)) => !matches!(value.trim(), "" | "0" | "-1"),
+        Some(_) => true,
+    }
+}
+
+/// Resource Limits Rule
+pub fn resource_limits(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = resource_limits_metadata();
+    for (name, service) in &compose_file.compose.services {
+        let limits = service
+            .deploy
+            .as_ref()
+            .and_then(|deploy| deploy.resources.as_ref())
+            .and_then(|resources| resources.limits.as_ref());
+
+        let checks = [
+            (
+                "MEMORY_LIMIT",
+                "memory",
+                limited(service.mem_limit.as_ref())
+                    || limited(limits.and_then(|l| l.memory.as_ref())),
+            ),
+            (
+                "CPU_LIMIT",
+                "CPU",
+                limited(service.cpus.as_ref())
+                    || limited(service.cpu_shares.as_ref())
+                    || limited(service.cpu_quota.as_ref())
+                    || limited(limits.and_then(|l| l.cpus.as_ref())),
+            ),
+            (
+                "PIDS_LIMIT",
+                "process",
+                limited(service.pids_limit.as_ref())
+                    || limited(limits.and_then(|l| l.pids.as_ref())),
+            ),
+        ];
+
+        for (id, resource, limited) in checks {
+            if !limited {
+                alerts.push(Alert {
+                    details: format!("No {resource} limit set for '{name}'"),
+                    path: compose_file.location(&format!("services.{name}")),
+                    ..alert(&metadata, id)
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the `read_only_filesystem` rule
+pub fn read_only_filesystem_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("READ_ONLY_FILESYSTEM".to_string()), "Writable root filesystem")
+            .description("A writable root filesystem lets an attacker change the binaries and configuration of the container.")
+            .severity(Severity::Low)
+            .tags(&["hardening"])
+            .cwe("732")
+            .owasp("D09")
+            .cis("5.13")
+            .remediation("Set `read_only: true`, and mount volumes or `tmpfs` for the paths the service writes to.")
+            .benchmark(),
+    ]
+}
+
+/// Read Only Filesystem Rule
+pub fn read_only_filesystem(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = read_only_filesystem_metadata();
+    for (name, service) in &compose_file.compose.services {
+        if service.read_only != Some(true) {
+            alerts.push(Alert {
+                details: format!("Root filesystem is writable for '{name}'"),
+                path: compose_file.location(&format!("services.{name}.read_only")),
+                ..alert(&metadata, "READ_ONLY_FILESYSTEM")
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the `healthcheck` rule
+pub fn healthcheck_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("HEALTHCHECK".to_string()), "No health check")
+            .description("Without a health check a container that stops working is still reported as running. Images can also define a `HEALTHCHECK`, which is not checked.")
+            .severity(Severity::Low)
+            .tags(&["hardening"])
+            .owasp("D07")
+            .cis("5.27")
+            .remediation("Add a `healthcheck` to the service (or a `HEALTHCHECK` to the image).")
+            .benchmark(),
+    ]
+}
+
+/// Service has a health check that is not disabled (`disable: true` or
+/// `test: ["NONE"]`)
+fn has_healthcheck(service: &Service) -> bool {
+    let Some(healthcheck) = &service.healthcheck else {
+        return false;
+    };
+    let none = match &healthcheck.test {
+        Some(StringOrList::VecStr(test)) => test.first().map(String::as_str) == Some("NONE"),
+        Some(StringOrList::Str(test)) => test == "NONE",
+        None => false,
+    };
+    healthcheck.disable != Some(true) && !none
+}
+
+/// Healthcheck Rule
+pub fn healthcheck(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    let metadata = healthcheck_metadata();
+    for (name, service) in &compose_file.compose.services {
+        if !has_healthcheck(service) {
+            alerts.push(Alert {
+                details: format!("No health check for '{name}'"),
+                path: compose_file.location(&format!("services.{name}.healthcheck")),
+                ..alert(&metadata, "HEALTHCHECK")
+            });
+        }
+    }
+    Ok(())
+}
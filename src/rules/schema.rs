@@ -0,0 +1,47 @@
+use anyhow::Result;
+
+use crate::{
+    compose::ComposeFile,
+    config::Config,
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
+};
+
+/// Alerts of the `schema_validation` rule
+pub fn schema_validation_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("SCHEMA_VIOLATION".to_string()), "Compose schema violation")
+            .description("Part of the compose file does not match the compose-spec schema, such as a misspelled key that Compose ignores or rejects.")
+            .severity(Severity::Medium)
+            .tags(&["correctness"])
+            .cwe("1286")
+            .owasp("D04")
+            .remediation("Fix the key or value, using the suggested key when there is one."),
+    ]
+}
+
+/// Schema Validation Rule
+///
+/// Unknown keys (typos like `privilaged`) and invalid values are silently
+/// dropped or rejected by Compose, so intended hardening might never apply
+pub fn schema_validation(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<Alert>,
+) -> Result<()> {
+    for violation in &compose_file.violations {
+        let details = match &violation.suggestion {
+            Some(suggestion) => format!("{}, did you mean `{suggestion}`?", violation.message),
+            None => violation.message.clone(),
+        };
+
+        alerts.push(Alert {
+            id: RuleID::Quibble("SCHEMA_VIOLATION".to_string()),
+            details,
+            severity: Severity::Medium,
+            path: compose_file.location(&violation.key),
+            ..Default::default()
+        });
+    }
+    Ok(())
+}
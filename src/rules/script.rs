@@ -0,0 +1,355 @@
+//! Rules written as [Rhai](https://rhai.rs) scripts
+//!
+//! Scripts (`*.rhai`) are loaded from the `script-rules` directory of the
+//! config. Each script is a rule named after its file and is run for every
+//! compose file with:
+//!
+//! - `compose`: the parsed compose file (ports and volumes use the long
+//!   syntax)
+//! - `path`: path of the compose file
+//! - `alert(id, message[, key[, severity]])`: report an alert at a compose key
+//! - `line(key)`: line of a compose key
+//!
+//! ```text
+//! // Services that publish ports and mount host paths must be read only
+//! for name in compose.services.keys() {
+//!     let service = compose.services[name];
+//!     if service.ports == () || service.read_only == true {
+//!         continue;
+//!     }
+//!     for volume in service.volumes {
+//!         if volume.type == "bind" {
+//!             alert("READ_ONLY_HOST_MOUNT", "Service `" + name + "` is not read only", "services." + name);
+//!             break;
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Scripts can not import modules, access files, the network or the
+//! environment, and are stopped when they exceed the `script-limits` of the
+//! config.
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, Map, Scope, AST,
+};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    compose::{self, ComposeFile},
+    config::{Config, ScriptLimits},
+    rules::{custom, Rule, RuleMetadata},
+    security::{Alert, AlertLocation, RuleID, Severity},
+};
+
+/// Extension of script rules
+const EXTENSION: &str = "rhai";
+
+/// Alert reported by a script with `alert()`
+struct ScriptAlert {
+    id: String,
+    message: String,
+    /// Compose key of the alert
+    key: Option<String>,
+    severity: Option<String>,
+}
+
+/// Rule defined by a script
+pub struct ScriptRule {
+    name: String,
+    path: PathBuf,
+    ast: AST,
+    limits: ScriptLimits,
+    metadata: Vec<RuleMetadata>,
+}
+
+/// Script rules in a directory, sorted by name
+pub fn find(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let entries = std::fs::read_dir(directory)
+        .with_context(|| format!("Unable to read script rules: {}", directory.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some(EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Sandboxed engine with the execution limits of the config
+fn engine(limits: &ScriptLimits) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(limits.max_operations)
+        .set_max_call_levels(limits.max_call_depth)
+        .set_max_string_size(limits.max_size)
+        .set_max_array_size(limits.max_size)
+        .set_max_map_size(limits.max_size)
+        .on_print(|text| debug!("Script rule: {text}"))
+        .on_debug(|text, _, position| debug!("Script rule ({position}): {text}"));
+    engine
+}
+
+impl ScriptRule {
+    /// Load and compile a script
+    pub fn load(path: &Path, limits: &ScriptLimits) -> Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        ScriptRule::new(name, path, &source, limits)
+    }
+
+    pub fn new(
+        name: impl ToString,
+        path: &Path,
+        source: &str,
+        limits: &ScriptLimits,
+    ) -> Result<Self> {
+        let ast = engine(limits).compile(source)?;
+        let mut rule = ScriptRule {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            ast,
+            limits: limits.clone(),
+            metadata: Vec::new(),
+        };
+        rule.metadata = rule.load_metadata()?;
+        Ok(rule)
+    }
+
+    /// Alerts described by the optional `fn metadata()` of the script, which
+    /// returns a map (or an array of maps) with the `id`, `title`,
+    /// `description`, `severity` and `remediation` of the alerts
+    fn load_metadata(&self) -> Result<Vec<RuleMetadata>> {
+        if !self.ast.iter_functions().any(|f| f.name == "metadata") {
+            return Ok(Vec::new());
+        }
+        // Only the function is called, not the statements of the script
+        let options = CallFnOptions::new().eval_ast(false);
+        let value: Dynamic = engine(&self.limits)
+            .call_fn_with_options(options, &mut Scope::new(), &self.ast, "metadata", ())
+            .map_err(|err| anyhow!("{err}"))?;
+        let entries = match value.clone().try_cast::<rhai::Array>() {
+            Some(items) => items,
+            None => vec![value],
+        };
+
+        entries
+            .into_iter()
+            .map(|entry| {
+                let entry = entry.try_cast::<Map>().unwrap_or_default();
+                let field = |name: &str| {
+                    entry
+                        .get(name)
+                        .and_then(|value| value.clone().into_string().ok())
+                };
+                let id = field("id").ok_or_else(|| anyhow!("metadata is missing an `id`"))?;
+
+                Ok(
+                    RuleMetadata::new(RuleID::Quibble(id.clone()), field("title").unwrap_or(id))
+                        .description(field("description").unwrap_or_default())
+                        .severity(
+                            field("severity")
+                                .map(Severity::from)
+                                .unwrap_or(Severity::Medium),
+                        )
+                        .tags(&["script"])
+                        .remediation(field("remediation").unwrap_or_default()),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Rule for ScriptRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> &[RuleMetadata] {
+        &self.metadata
+    }
+
+    fn run(
+        &self,
+        _config: &Config,
+        compose_file: &ComposeFile,
+        alerts: &mut Vec<Alert>,
+    ) -> Result<()> {
+        let reported: Rc<RefCell<Vec<ScriptAlert>>> = Rc::default();
+        let mut engine = engine(&self.limits);
+
+        // `alert(id, message[, key[, severity]])`, the key and severity can
+        // also be `()`
+        let alert = |reported: &Rc<RefCell<Vec<ScriptAlert>>>| {
+            let reported = reported.clone();
+            move |id: &str, message: &str, key: Dynamic, severity: Dynamic| {
+                reported.borrow_mut().push(ScriptAlert {
+                    id: id.to_string(),
+                    message: message.to_string(),
+                    key: key.into_string().ok(),
+                    severity: severity.into_string().ok(),
+                })
+            }
+        };
+        let report = alert(&reported);
+        engine.register_fn("alert", move |id: &str, message: &str| {
+            report(id, message, Dynamic::UNIT, Dynamic::UNIT)
+        });
+        let report = alert(&reported);
+        engine.register_fn("alert", move |id: &str, message: &str, key: Dynamic| {
+            report(id, message, key, Dynamic::UNIT)
+        });
+        engine.register_fn("alert", alert(&reported));
+
+        let mappings = compose_file.mappings.clone();
+        engine.register_fn("line", move |key: &str| -> Dynamic {
+            match compose::closest(&mappings, key).and_then(|key| mappings.get(key)) {
+                Some(region) => Dynamic::from(region.start_line as i64 + 1),
+                None => Dynamic::UNIT,
+            }
+        });
+
+        let mut scope = Scope::new();
+        let compose =
+            rhai::serde::to_dynamic(&compose_file.compose).map_err(|err| anyhow!("{err}"))?;
+        scope.push_constant("compose", compose);
+        scope.push_constant("path", compose_file.path.display().to_string());
+        engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|err| anyhow!("Script rule {}: {err}", self.path.display()))?;
+
+        for alert in reported.take() {
+            let severity = match alert.severity {
+                Some(severity) => Severity::from(severity),
+                None => self
+                    .metadata
+                    .iter()
+                    .find(|m| m.id.to_string() == alert.id)
+                    .map(|m| m.severity)
+                    .unwrap_or(Severity::Medium),
+            };
+            let (path, value) = match &alert.key {
+                Some(key) => (compose_file.location(key), custom::value(compose_file, key)),
+                None => (
+                    AlertLocation {
+                        path: compose_file.path.clone(),
+                        ..Default::default()
+                    },
+                    None,
+                ),
+            };
+
+            alerts.push(Alert {
+                id: RuleID::Quibble(alert.id),
+                details: alert.message,
+                severity,
+                path,
+                value,
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::compose_file;
+
+    #[test]
+    fn script_rule() {
+        let (_dir, compose_file) = compose_file("services:\n  web:\n    image: nginx\n    ports:\n      - 8080:80\n    volumes:\n      - ./html:/usr/share/nginx/html\n  cache:\n    image: redis\n    volumes:\n      - ./data:/data\n  api:\n    image: api\n    read_only: true\n    ports:\n      - 3000:3000\n    volumes:\n      - ./config:/config\n",
+        );
+        let source = "fn metadata() {\n    #{ id: \"READ_ONLY_HOST_MOUNT\", title: \"Host mount on a published service\", severity: \"high\" }\n}\n\nfor name in compose.services.keys() {\n    let service = compose.services[name];\n    if service.ports == () || service.read_only == true {\n        continue;\n    }\n    for volume in service.volumes {\n        if volume.type == \"bind\" {\n            alert(\"READ_ONLY_HOST_MOUNT\", \"Service `\" + name + \"` is not read only\", \"services.\" + name);\n            break;\n        }\n    }\n}\n";
+
+        let rule = ScriptRule::new(
+            "read_only",
+            Path::new("read_only.rhai"),
+            source,
+            &ScriptLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            rule.metadata()[0].title,
+            "Host mount on a published service"
+        );
+
+        let mut alerts = Vec::new();
+        rule.run(&Config::default(), &compose_file, &mut alerts)
+            .unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].details, "Service `web` is not read only");
+        assert_eq!(alerts[0].severity, Severity::High);
+        assert_eq!(alerts[0].path.line(), Some(1));
+
+        // Errors name the script and line
+        let rule = ScriptRule::new(
+            "broken",
+            Path::new("broken.rhai"),
+            "let x = 1;\nx.missing.call();",
+            &ScriptLimits::default(),
+        )
+        .unwrap();
+        let err = rule
+            .run(&Config::default(), &compose_file, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Script rule broken.rhai: Unknown property 'missing' - a getter is not registered for type 'i64' (line 2, position 3)"
+        );
+    }
+
+    #[test]
+    fn script_values() {
+        let (_dir, compose_file) = compose_file("services:\n  web:\n    image: nginx:latest\n");
+        let rule = ScriptRule::new(
+            "image",
+            Path::new("image.rhai"),
+            "let image = compose.services.web.image;\nalert(\"IMAGE\", \"Image \" + image + \" on line \" + line(\"services.web.image\"), \"services.web.image\", \"low\");\n",
+            &ScriptLimits::default(),
+        )
+        .unwrap();
+        let mut alerts = Vec::new();
+        rule.run(&Config::default(), &compose_file, &mut alerts)
+            .unwrap();
+
+        assert_eq!(alerts[0].details, "Image nginx:latest on line 3");
+        assert_eq!(alerts[0].severity, Severity::Low);
+        assert_eq!(alerts[0].value.as_deref(), Some("nginx:latest"));
+    }
+
+    #[test]
+    fn script_sandbox() {
+        let (_dir, compose_file) = compose_file("services: {}\n");
+        let run = |source: &str| {
+            let limits = ScriptLimits {
+                max_operations: 10_000,
+                ..Default::default()
+            };
+            ScriptRule::new("sandbox", Path::new("sandbox.rhai"), source, &limits)?.run(
+                &Config::default(),
+                &compose_file,
+                &mut Vec::new(),
+            )
+        };
+
+        let err = run("loop { }").unwrap_err();
+        assert!(err.to_string().contains("Too many operations"), "{err}");
+        assert!(run("import \"other\" as other;").is_err());
+        assert!(run("eval(\"1 + 1\")").is_err());
+        assert!(run("let text = \"x\"; loop { text += text; }").is_err());
+    }
+}
@@ -6,9 +6,28 @@ use log::debug;
 use crate::{
     compose::ComposeFile,
     config::Config,
-    security::{Alert, AlertLocation, RuleID, Severity},
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
 };
 
+/// Host paths of the Docker daemon socket
+const DOCKER_SOCKETS: &[&str] = &["/var/run/docker.sock", "/run/docker.sock"];
+
+/// Alerts of the `docker_socket` rule
+pub fn docker_socket_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("DOCKER_SOCKET".to_string()), "Docker socket mounted into a container")
+            .description("Access to the Docker daemon socket gives a container full control of the host, even when the socket is mounted read-only.")
+            .severity(Severity::High)
+            .tags(&["security"])
+            .cwe("250")
+            .cwe("668")
+            .owasp("D04")
+            .cis("5.32")
+            .remediation("Remove the socket volume, or put a filtering proxy (such as a Docker socket proxy) in front of the daemon."),
+    ]
+}
+
 /// Docker Socket Rule
 pub fn docker_socket(
     _config: &Config,
@@ -19,23 +38,25 @@ pub fn docker_socket(
 
     for (name, service) in &compose_file.compose.services {
         if let Some(volumes) = &service.volumes {
-            let result = volumes
-                .iter()
-                .find(|&s| s.starts_with("/var/run/docker.sock"));
+            let result = volumes.iter().enumerate().find(|(_, v)| {
+                v.host_path()
+                    .map(|p| DOCKER_SOCKETS.contains(&p.trim_end_matches('/')))
+                    .unwrap_or(false)
+            });
 
-            if result.is_some() {
-                let mapping_line = compose_file
-                    .mappings
-                    .get(format!("services.{}.volumes", name).as_str());
+            if let Some((index, volume)) = result {
+                let details = match volume.is_read_only() {
+                    true => String::from("Docker Socket being passed into container (read-only)"),
+                    false => String::from("Docker Socket being passed into container"),
+                };
 
                 alerts.push(Alert {
                     id: RuleID::Quibble("DOCKER_SOCKET".to_string()),
-                    details: String::from("Docker Socket being passed into container"),
+                    details,
                     severity: Severity::High,
-                    path: AlertLocation {
-                        path: compose_file.path.clone(),
-                        line: mapping_line.copied(),
-                    },
+                    path: compose_file.location(&format!("services.{name}.volumes[{index}]")),
+                    value: volume.host_path().map(String::from),
+                    ..Default::default()
                 })
             }
         }
@@ -0,0 +1,57 @@
+use anyhow::Result;
+
+use crate::{
+    compose::ComposeFile,
+    config::Config,
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
+};
+
+/// Alerts of the `unresolved_variables` rule
+pub fn unresolved_variables_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("UNRESOLVED_VARIABLE".to_string()), "Unresolved variable")
+            .description("A variable used in the compose file is not set. Required variables stop the project from starting, other variables become an empty string.")
+            .severity(Severity::Medium)
+            .tags(&["correctness"])
+            .cwe("1188")
+            .owasp("D04")
+            .remediation("Set the variable in the `.env` file or environment, or give it a default (`${VAR:-default}`)."),
+    ]
+}
+
+/// Unresolved Variables Rule
+///
+/// Required variables (`${VAR:?err}`) without a value stop the project from
+/// starting, while unset variables silently become an empty string
+pub fn unresolved_variables(
+    _config: &Config,
+    compose_file: &ComposeFile,
+    alerts: &mut Vec<crate::security::Alert>,
+) -> Result<()> {
+    for variable in &compose_file.unresolved {
+        let (details, severity) = match &variable.message {
+            Some(message) => (
+                format!("Unresolved variable in compose file: {message}"),
+                Severity::Medium,
+            ),
+            None => (
+                format!(
+                    "Variable `{}` is not set, defaulting to a blank string",
+                    variable.variable
+                ),
+                Severity::Low,
+            ),
+        };
+
+        alerts.push(Alert {
+            id: RuleID::Quibble("UNRESOLVED_VARIABLE".to_string()),
+            details,
+            severity,
+            path: compose_file.location(&variable.key),
+            value: Some(variable.variable.clone()),
+            ..Default::default()
+        });
+    }
+    Ok(())
+}
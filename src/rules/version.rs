@@ -4,9 +4,34 @@ use log::debug;
 use crate::{
     compose::ComposeFile,
     config::Config,
-    security::{Alert, AlertLocation, RuleID, Severity},
+    rules::RuleMetadata,
+    security::{Alert, RuleID, Severity},
 };
 
+/// Alerts of the `docker_version` rule
+pub fn docker_version_metadata() -> Vec<RuleMetadata> {
+    vec![
+        RuleMetadata::new(RuleID::Quibble("COMPOSE_V1".to_string()), "Compose file format v1")
+            .description("Version 1 of the Compose file format is deprecated and no longer supported by Docker Compose.")
+            .severity(Severity::Medium)
+            .tags(&["maintenance"])
+            .owasp("D02")
+            .remediation("Remove the `version` key and migrate the file to the Compose Specification."),
+        RuleMetadata::new(RuleID::Quibble("COMPOSE_V2".to_string()), "Compose file format v2")
+            .description("Version 2 of the Compose file format is legacy and superseded by the Compose Specification.")
+            .severity(Severity::Low)
+            .tags(&["maintenance"])
+            .owasp("D02")
+            .remediation("Remove the `version` key, Docker Compose uses the Compose Specification by default."),
+        RuleMetadata::new(RuleID::Quibble("COMPOSE_V3".to_string()), "Old Compose file format v3")
+            .description("Early versions of the v3 Compose file format are legacy and superseded by the Compose Specification.")
+            .severity(Severity::Low)
+            .tags(&["maintenance"])
+            .owasp("D02")
+            .remediation("Remove the `version` key, Docker Compose uses the Compose Specification by default."),
+    ]
+}
+
 /// Check which compose spec version is being used
 pub fn docker_version(
     _config: &Config,
@@ -21,28 +46,25 @@ pub fn docker_version(
                 id: RuleID::Quibble("COMPOSE_V1".to_string()),
                 details: String::from("Compose v1"),
                 severity: Severity::Medium,
-                path: AlertLocation {
-                    path: compose_file.path.clone(),
-                    line: compose_file.mappings.get("version").copied(),
-                },
+                path: compose_file.location("version"),
+                value: Some(version.clone()),
+                ..Default::default()
             }),
             "2" | "2.0" | "2.1" | "2.2" | "2.3" | "2.4" => alerts.push(Alert {
                 id: RuleID::Quibble("COMPOSE_V2".to_string()),
                 details: String::from("Compose v2 used"),
                 severity: Severity::Low,
-                path: AlertLocation {
-                    path: compose_file.path.clone(),
-                    line: compose_file.mappings.get("version").copied(),
-                },
+                path: compose_file.location("version"),
+                value: Some(version.clone()),
+                ..Default::default()
             }),
             "3" | "3.0" | "3.1" | "3.2" | "3.3" | "3.4" | "3.5" => alerts.push(Alert {
                 id: RuleID::Quibble("COMPOSE_V3".to_string()),
                 details: String::from("Using old Compose v3 spec, consider upgrading"),
                 severity: Severity::Low,
-                path: AlertLocation {
-                    path: compose_file.path.clone(),
-                    line: compose_file.mappings.get("version").copied(),
-                },
+                path: compose_file.location("version"),
+                value: Some(version.clone()),
+                ..Default::default()
             }),
             _ => {
                 debug!("Unknown or secure version of Docker Compose")
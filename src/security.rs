@@ -1,7 +1,7 @@
 #![allow(unused)]
-use std::{cell::RefCell, fmt::Display, ops::Index, path::PathBuf, rc::Rc};
+use std::{cell::RefCell, collections::BTreeSet, fmt::Display, ops::Index, path::PathBuf, rc::Rc};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::{error, warn};
 
 use crate::{compose::ComposeFile, config::Config, rules::*, security};
@@ -19,7 +19,7 @@ const SEVERITIES: &[&str; 10] = &[
     "all",
 ];
 
-#[derive(Debug, Default, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Ord, PartialOrd)]
 /// Severity for the alert
 pub enum Severity {
     Critical,
@@ -53,6 +53,18 @@ impl Severity {
 
         di <= fl
     }
+
+    /// CVSS score used as the SARIF `security-severity`
+    pub fn cvss(&self) -> String {
+        match self {
+            Severity::Critical => "10.0".to_string(),
+            Severity::High => "7.0".to_string(),
+            Severity::Medium => "5.0".to_string(),
+            Severity::Low => "3.0".to_string(),
+            Severity::Hardening => "2.0".to_string(),
+            Severity::Information | Severity::All | Severity::Quality => "".to_string(),
+        }
+    }
 }
 
 impl From<String> for Severity {
@@ -88,14 +100,69 @@ impl Display for Severity {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Rule ID using CWE or OWASP Docker Top 10
+///
+/// Alerts use `Quibble` IDs, CWE and OWASP IDs are the references of a rule.
 pub enum RuleID {
     Quibble(String),
     Cwe(String),
     Owasp(String),
 }
 
+/// OWASP Docker Top 10 categories
+/// (https://owasp.org/www-project-docker-top-10/)
+pub const OWASP_DOCKER_TOP_10: [(&str, &str); 10] = [
+    ("D01", "Secure User Mapping"),
+    ("D02", "Patch Management Strategy"),
+    ("D03", "Network Segmentation and Firewalling"),
+    ("D04", "Secure Defaults and Hardening"),
+    ("D05", "Maintain Security Contexts"),
+    ("D06", "Protect Secrets"),
+    ("D07", "Resource Protection"),
+    ("D08", "Container Image Integrity and Origin"),
+    ("D09", "Follow Immutable Paradigm"),
+    ("D10", "Logging"),
+];
+
+impl RuleID {
+    /// CWE reference, from `250` or `CWE-250`
+    pub fn cwe(id: &str) -> Result<Self> {
+        let id = id.trim();
+        let number = id
+            .strip_prefix("CWE-")
+            .or_else(|| id.strip_prefix("cwe-"))
+            .unwrap_or(id);
+        match !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+            true => Ok(RuleID::Cwe(number.to_string())),
+            false => Err(anyhow!("invalid CWE `{id}`")),
+        }
+    }
+
+    /// OWASP Docker Top 10 reference, from `D04` or `OWASP-D04`
+    pub fn owasp(id: &str) -> Result<Self> {
+        let id = id.trim();
+        let category = id.strip_prefix("OWASP-").unwrap_or(id).to_uppercase();
+        match OWASP_DOCKER_TOP_10.iter().any(|(c, _)| *c == category) {
+            true => Ok(RuleID::Owasp(category)),
+            false => Err(anyhow!(
+                "invalid OWASP Docker Top 10 category `{id}` (D01 to D10)"
+            )),
+        }
+    }
+
+    /// Name of an OWASP Docker Top 10 category
+    pub fn title(&self) -> Option<&'static str> {
+        match self {
+            RuleID::Owasp(id) => OWASP_DOCKER_TOP_10
+                .iter()
+                .find(|(c, _)| c == id)
+                .map(|(_, title)| *title),
+            _ => None,
+        }
+    }
+}
+
 impl Default for RuleID {
     fn default() -> Self {
         RuleID::Quibble("N/A".to_string())
@@ -126,8 +193,50 @@ pub struct Alert {
     pub severity: Severity,
     /// Alert ID
     pub id: RuleID,
-    /// Alert Location
+    /// Alert Location, including the compose key (`services.web.cap_add[1]`)
     pub path: AlertLocation,
+    /// Name of the service the alert belongs to
+    pub service: Option<String>,
+    /// Value that triggered the alert, such as the image or capability
+    pub value: Option<String>,
+    /// Set when the alert was waived with a `quibble-ignore` comment
+    pub suppression: Option<Suppression>,
+    /// Set when the alert was compared to a baseline
+    pub baseline: Option<BaselineState>,
+    /// Suggested fix, applied with `--fix`
+    pub fix: Option<Fix>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Suggested fix for an alert, as edits of the source files
+pub struct Fix {
+    /// What the fix changes, such as "Remove `privileged: true`"
+    pub description: String,
+    pub edits: Vec<Edit>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Replace a region of a file with `text`, an empty region inserts `text`
+pub struct Edit {
+    pub path: PathBuf,
+    pub region: Region,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// State of an alert compared to a baseline
+pub enum BaselineState {
+    /// Alert is not in the baseline
+    New,
+    /// Alert is in the baseline
+    Unchanged,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Alert suppressed with a `quibble-ignore` comment
+pub struct Suppression {
+    /// Reason given for the suppression
+    pub justification: Option<String>,
 }
 
 impl Alert {
@@ -138,14 +247,7 @@ impl Alert {
     }
 
     pub fn cvss(&self) -> String {
-        match self.severity {
-            Severity::Critical => "10.0".to_string(),
-            Severity::High => "7.0".to_string(),
-            Severity::Medium => "5.0".to_string(),
-            Severity::Low => "3.0".to_string(),
-            Severity::Hardening => "2.0".to_string(),
-            Severity::Information | Severity::All | Severity::Quality => "".to_string(),
-        }
+        self.severity.cvss()
     }
 }
 
@@ -159,18 +261,92 @@ impl Display for Alert {
     }
 }
 
+/// Remove identical alerts that are reported more than once at the same
+/// location, such as the alerts of a base file that is part of several
+/// projects. Different alerts of a rule on the same key are kept.
+pub fn dedup(alerts: &mut Vec<Alert>) {
+    let mut seen = BTreeSet::new();
+    alerts.retain(|alert| {
+        seen.insert((
+            alert.id.to_string(),
+            alert.path.path.clone(),
+            alert.path.region,
+            alert.path.key.clone(),
+            alert.details.clone(),
+            alert.value.clone(),
+        ))
+    });
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// Problem that stopped Quibble from checking a file, such as a compose file
+/// that fails to parse or a rule that errors
+pub struct Diagnostic {
+    /// Details of the problem
+    pub message: String,
+    /// Location of the problem
+    pub path: AlertLocation,
+    /// Name of the rule that failed
+    pub rule: Option<String>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Diagnostic('{}', '{}')", self.message, self.path)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Region of a file, lines and columns are zero based
+pub struct Region {
+    pub start_line: i32,
+    pub start_column: i32,
+    pub end_line: i32,
+    pub end_column: i32,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
-/// Alert Location with a path and line number
+/// YAML anchor a value was pulled in from with an alias or merge key
+pub struct Anchor {
+    /// Key the anchored node is defined at (`x-common`)
+    pub key: String,
+    /// Key the anchor was pulled into (`services.web`)
+    pub target: String,
+    /// Region of the alias (`*common`) or merge key (`<<: *common`)
+    pub alias: Region,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Alert Location with a path and region in the file
 pub struct AlertLocation {
     pub path: PathBuf,
-    pub line: Option<i32>,
+    pub region: Option<Region>,
+    /// Anchor the value came from, `region` then points at the anchor
+    pub anchor: Option<Anchor>,
+    /// Compose profiles of the service the location belongs to
+    pub profiles: Vec<String>,
+    /// Compose key of the location (`services.web.cap_add[1]`)
+    pub key: Option<String>,
+}
+
+impl AlertLocation {
+    /// Zero based line the alert starts on
+    pub fn line(&self) -> Option<i32> {
+        self.region.map(|r| r.start_line)
+    }
 }
 
 impl Display for AlertLocation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.line {
-            Some(l) => {
-                write!(f, "{}#{}", self.path.display(), l + 1)
+        match self.region {
+            Some(r) => {
+                write!(
+                    f,
+                    "{}#{}:{}",
+                    self.path.display(),
+                    r.start_line + 1,
+                    r.start_column + 1
+                )
             }
             None => {
                 write!(f, "{}", self.path.display())
@@ -181,9 +357,11 @@ impl Display for AlertLocation {
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+
     use crate::{
         compose::ComposeFile,
-        security::{Alert, Rules, Severity},
+        security::{Alert, AlertLocation, Region, RuleID, Rules, Severity},
     };
 
     #[test]
@@ -209,6 +387,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dedup_alerts() {
+        let alert = |id: &str, line: i32| Alert {
+            id: RuleID::Quibble(id.to_string()),
+            path: AlertLocation {
+                path: PathBuf::from("compose.yml"),
+                region: Some(Region {
+                    start_line: line,
+                    ..Default::default()
+                }),
+                key: Some(String::from("services.web.privileged")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut alerts = vec![
+            alert("PRIVILEGED_CONTAINER", 3),
+            alert("SECURITY_OPTS", 2),
+            alert("PRIVILEGED_CONTAINER", 3),
+            alert("PRIVILEGED_CONTAINER", 5),
+        ];
+        super::dedup(&mut alerts);
+
+        assert_eq!(alerts.len(), 3);
+        assert_eq!(alerts[2].path.line(), Some(5));
+
+        // Two unresolved variables in one value
+        let unresolved = |variable: &str| Alert {
+            details: format!("Variable `{variable}` is not set"),
+            ..alert("UNRESOLVED_VARIABLE", 4)
+        };
+        let mut alerts = vec![
+            unresolved("FIRST_UNSET"),
+            unresolved("SECOND_UNSET"),
+            unresolved("FIRST_UNSET"),
+        ];
+        super::dedup(&mut alerts);
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[1].details, "Variable `SECOND_UNSET` is not set");
+    }
+
     #[test]
     fn filter() {
         assert!(Severity::Medium.filter(String::from("medium")));